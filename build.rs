@@ -1,4 +1,14 @@
+use std::env;
+use std::path::PathBuf;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("proto/auth.proto")?;
+    // The descriptor set lets the server answer reflection requests for the zkp_auth package
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("zkp_auth_descriptor.bin"))
+        .compile(
+            &["proto/auth.proto", "proto/google/rpc/status.proto", "proto/google/rpc/error_details.proto"],
+            &["proto"]
+        )?;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,35 @@
+CREATE TABLE users (
+    id TEXT PRIMARY KEY,
+    y1 BYTEA NOT NULL,
+    y2 BYTEA NOT NULL,
+    salt BYTEA NOT NULL,
+    memory_cost BIGINT NOT NULL,
+    time_cost BIGINT NOT NULL,
+    parallelism BIGINT NOT NULL,
+    auth_id TEXT,
+    session_id TEXT
+);
+
+CREATE TABLE challenges (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    c BYTEA NOT NULL,
+    r1 BYTEA NOT NULL,
+    r2 BYTEA NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at BIGINT NOT NULL
+);
+
+CREATE TABLE proofs (
+    id TEXT PRIMARY KEY,
+    expires_at BIGINT NOT NULL
+);
+
+CREATE INDEX challenges_expires_at ON challenges(expires_at);
+CREATE INDEX sessions_expires_at ON sessions(expires_at);
+CREATE INDEX proofs_expires_at ON proofs(expires_at);
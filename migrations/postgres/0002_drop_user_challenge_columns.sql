@@ -0,0 +1,3 @@
+ALTER TABLE users DROP COLUMN auth_id, DROP COLUMN session_id;
+
+CREATE INDEX challenges_user_id ON challenges(user_id);
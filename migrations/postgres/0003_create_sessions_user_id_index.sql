@@ -0,0 +1 @@
+CREATE INDEX sessions_user_id ON sessions(user_id);
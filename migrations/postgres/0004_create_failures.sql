@@ -0,0 +1,8 @@
+CREATE TABLE failures (
+    id TEXT PRIMARY KEY,
+    count BIGINT NOT NULL,
+    last_failure BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL
+);
+
+CREATE INDEX failures_expires_at ON failures(expires_at);
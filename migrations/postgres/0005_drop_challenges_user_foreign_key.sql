@@ -0,0 +1 @@
+ALTER TABLE challenges DROP CONSTRAINT challenges_user_id_fkey;
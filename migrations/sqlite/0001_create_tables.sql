@@ -0,0 +1,35 @@
+CREATE TABLE users (
+    id TEXT PRIMARY KEY NOT NULL,
+    y1 BLOB NOT NULL,
+    y2 BLOB NOT NULL,
+    salt BLOB NOT NULL,
+    memory_cost INTEGER NOT NULL,
+    time_cost INTEGER NOT NULL,
+    parallelism INTEGER NOT NULL,
+    auth_id TEXT,
+    session_id TEXT
+);
+
+CREATE TABLE challenges (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    c BLOB NOT NULL,
+    r1 BLOB NOT NULL,
+    r2 BLOB NOT NULL,
+    expires_at INTEGER NOT NULL
+);
+
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at INTEGER NOT NULL
+);
+
+CREATE TABLE proofs (
+    id TEXT PRIMARY KEY NOT NULL,
+    expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX challenges_expires_at ON challenges(expires_at);
+CREATE INDEX sessions_expires_at ON sessions(expires_at);
+CREATE INDEX proofs_expires_at ON proofs(expires_at);
@@ -0,0 +1,4 @@
+ALTER TABLE users DROP COLUMN auth_id;
+ALTER TABLE users DROP COLUMN session_id;
+
+CREATE INDEX challenges_user_id ON challenges(user_id);
@@ -0,0 +1,8 @@
+CREATE TABLE failures (
+    id TEXT PRIMARY KEY NOT NULL,
+    count INTEGER NOT NULL,
+    last_failure INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX failures_expires_at ON failures(expires_at);
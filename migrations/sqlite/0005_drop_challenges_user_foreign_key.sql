@@ -0,0 +1,16 @@
+-- SQLite cannot drop a constraint, so the table is rebuilt without it
+CREATE TABLE challenges_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    c BLOB NOT NULL,
+    r1 BLOB NOT NULL,
+    r2 BLOB NOT NULL,
+    expires_at INTEGER NOT NULL
+);
+
+INSERT INTO challenges_new (id, user_id, c, r1, r2, expires_at) SELECT id, user_id, c, r1, r2, expires_at FROM challenges;
+DROP TABLE challenges;
+ALTER TABLE challenges_new RENAME TO challenges;
+
+CREATE INDEX challenges_user_id ON challenges(user_id);
+CREATE INDEX challenges_expires_at ON challenges(expires_at);
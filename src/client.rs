@@ -2,10 +2,6 @@ use auth_lib::bootstrap_client;
 use auth_lib::grpc::chaum_pedersen_client::AuthClient;
 use clap::{Parser, Subcommand};
 
-pub mod auth_client {
-    tonic::include_proto!("zkp_auth"); // The string specified here must match the proto package name
-}
-
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
@@ -35,6 +31,79 @@ enum Commands {
         /// Turn debugging information on
         #[arg(short, long)]
         password: String,
+
+        /// Authenticate with a single request using a non-interactive proof
+        #[arg(long)]
+        non_interactive: bool,
+    },
+
+    /// replaces the password after proving the knowledge of the current one and ends all sessions of the user
+    ChangePassword {
+        /// Sets the user name
+        #[arg(short, long)]
+        name: String,
+
+        /// Sets the current password
+        #[arg(short, long)]
+        password: String,
+
+        /// Sets the new password
+        #[arg(long)]
+        new_password: String,
+    },
+
+    /// deletes the user together with its sessions
+    Unregister {
+        /// Sets the user name
+        #[arg(short, long)]
+        name: String,
+
+        /// Proves the knowledge of the password
+        #[arg(short, long, required_unless_present = "admin_token")]
+        password: Option<String>,
+
+        /// Deletes the user without a proof, as configured in ADMIN_TOKEN on the server
+        #[arg(long, conflicts_with = "password")]
+        admin_token: Option<String>,
+    },
+
+    /// checks whether the session is still valid and prints its user
+    Validate {
+        /// Sets the session token
+        #[arg(short, long)]
+        session: String,
+    },
+
+    /// replaces the session with a new one and returns the new session token
+    Refresh {
+        /// Sets the session token
+        #[arg(short, long)]
+        session: String,
+    },
+
+    /// ends the session
+    Logout {
+        /// Sets the session token
+        #[arg(short, long)]
+        session: String,
+    },
+
+    /// lists the active sessions of the user of the session
+    Sessions {
+        /// Sets the session token
+        #[arg(short, long)]
+        session: String,
+    },
+
+    /// ends another session of the user of the session
+    Revoke {
+        /// Sets the session token
+        #[arg(short, long)]
+        session: String,
+
+        /// Sets the reference of the session to end, as printed by sessions
+        #[arg(short = 'r', long)]
+        session_ref: String,
     },
 }
 
@@ -43,26 +112,82 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
  
     println!("Connect to server");
-    let config = auth_lib::Config::build();
-    let client = bootstrap_client::<Box<dyn AuthClient>, String>(format!("http://{}:{}", config.host, config.port)).await?;
+    let config = auth_lib::Config::build()?;
+    let client = bootstrap_client::<Box<dyn AuthClient>, String>(config.server_url(), Some(config)).await?;
     
     match cli.command {
         Commands::Register { name, password } => {
             println!("Register user");
 
-            let x = auth_lib::calculate_hash(&password);
-            client.register_user(&name, &x).await?;
+            client.register_user(&name, &password).await?;
             
             println!("User registered");
         },
-        Commands::Login { name, password } => {
+        Commands::Login { name, password, non_interactive } => {
             println!("Authenticate user");
 
-            let x = auth_lib::calculate_hash(&password);
-            let session_token = client.authenticate_user(&name, &x).await?;
+            let session_token = if non_interactive {
+                client.authenticate_user_non_interactive(&name, &password).await?
+            } else {
+                client.authenticate_user(&name, &password).await?
+            };
+
+            println!("Received session {:?}", session_token);
+        },
+        Commands::ChangePassword { name, password, new_password } => {
+            println!("Change password");
+
+            client.change_password(&name, &password, &new_password).await?;
+
+            println!("Password changed");
+        },
+        Commands::Unregister { name, password, admin_token } => {
+            println!("Unregister user");
+
+            match (password, admin_token) {
+                (_, Some(admin_token)) => client.remove_user(&name, &admin_token).await?,
+                (Some(password), None) => client.unregister_user(&name, &password).await?,
+                (None, None) => return Err("Either the password or the admin token is required".into())
+            }
+
+            println!("User unregistered");
+        },
+        Commands::Validate { session } => {
+            println!("Validate session");
+
+            let user = client.validate_session(&session).await?;
+
+            println!("Session belongs to user {:?}", user);
+        },
+        Commands::Refresh { session } => {
+            println!("Refresh session");
+
+            let session_token = client.refresh_session(&session).await?;
 
             println!("Received session {:?}", session_token);
         },
+        Commands::Logout { session } => {
+            println!("Logout");
+
+            client.logout(&session).await?;
+
+            println!("Session ended");
+        },
+        Commands::Sessions { session } => {
+            println!("List sessions");
+
+            for info in client.list_sessions(&session).await? {
+                let current = if info.current { " (current)" } else { "" };
+                println!("{} expires at {}{}", info.session_ref, info.expires_at, current);
+            }
+        },
+        Commands::Revoke { session, session_ref } => {
+            println!("Revoke session");
+
+            client.revoke_session(&session, &session_ref).await?;
+
+            println!("Session revoked");
+        },
     }
 
     Ok(())
@@ -1 +1,6 @@
-pub mod algorithm;
\ No newline at end of file
+pub mod algorithm;
+pub mod encoding;
+pub mod group;
+pub mod named_group;
+pub mod secret;
+pub mod validation;
\ No newline at end of file
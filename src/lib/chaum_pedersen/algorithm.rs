@@ -1,61 +1,147 @@
-use std::{sync::RwLock, str::FromStr};
+use std::{sync::{PoisonError, RwLock}, str::FromStr};
 use std::collections::HashMap;
 use num_bigint::{ToBigInt, BigInt, RandBigInt, Sign, BigUint};
-use num_primes::{Generator};
-use rand::{rngs::StdRng, SeedableRng};
+use num_traits::{One, Zero};
+use num_primes::Generator;
 use once_cell::sync::Lazy;
+use sha2::{Digest, Sha512};
+
+use super::group::{Group, GroupType, ModPGroup, Ristretto255Group};
+use super::named_group::NamedGroup;
+
+// Public seed from which the generators g and h are derived. Anyone can recompute them from it,
+// which shows that nobody chose them such that the discrete logarithm of h base g is known.
+pub const DEFAULT_GENERATOR_SEED: &[u8] = b"zkp_auth Chaum-Pedersen generators";
+const GENERATOR_LABEL: &[u8] = b"zkp_auth Chaum-Pedersen generator";
+const FIAT_SHAMIR_LABEL: &[u8] = b"zkp_auth Chaum-Pedersen Fiat-Shamir challenge";
 
 static FIXED_PARAMETERS: Lazy<RwLock<HashMap<u16, ChaumPedersenParameters>>> = Lazy::new(|| {
     let mut m = HashMap::new();
-    m.insert(256, ChaumPedersenParameters{
-        p: BigInt::from_str("42765216643065397982265462252423826320512529931694366715111734768493812630447").unwrap(),
-        q: BigInt::from_str("21382608321532698991132731126211913160256264965847183357555867384246906315223").unwrap(),
-        g: BigInt::from_str("4").unwrap(),
-        h: BigInt::from_str("9").unwrap(),
-        bit_size: 256
-    });
+    // Only meant for tests, p has 255 bits and is rejected unless the minimum bit size is lowered explicitly
+    m.insert(256, ChaumPedersenParameters::with_generators(
+        BigInt::from_str("42765216643065397982265462252423826320512529931694366715111734768493812630447").unwrap(),
+        BigInt::from_str("21382608321532698991132731126211913160256264965847183357555867384246906315223").unwrap(),
+        256,
+        GroupType::ModP,
+        DEFAULT_GENERATOR_SEED
+    ));
+    // Bigger groups take minutes to generate, so the RFC 7919 groups are used instead
+    for bit_size in [2048, 3072, 4096, 6144, 8192] {
+        let group = NamedGroup::fixed(bit_size).expect("every RFC 7919 size has a group");
+        m.insert(bit_size, group.parameters());
+    }
     RwLock::new(m)
 });
 
+static RISTRETTO255_PARAMETERS: Lazy<ChaumPedersenParameters> = Lazy::new(|| {
+    // p is the order of the underlying field, q the order of the prime-order group
+    let p = (BigInt::from(1) << 255) - 19;
+    let q = (BigInt::from(1) << 252) + BigInt::from_str("27742317777372353535851937790883648493").unwrap();
+    ChaumPedersenParameters::with_generators(p, q, 256, GroupType::Ristretto255, DEFAULT_GENERATOR_SEED)
+});
+
 pub trait ChaumPedersen {
     fn get_parameters(&self) -> &ChaumPedersenParameters;
     fn exponentiation(&self, x: &BigInt) -> (BigInt, BigInt);
-    fn generate_random(&mut self) -> BigInt;
+    fn generate_random(&self) -> BigInt;
+    fn is_scalar(&self, value: &BigInt) -> bool;
+    fn is_element(&self, value: &BigInt) -> bool;
     fn solve_challenge(&self, x: &BigInt, k: &BigInt, c: &BigInt) -> BigInt;
     fn verify(&self, y1: &BigInt, y2: &BigInt, r1: &BigInt, r2: &BigInt, s: &BigInt, c: &BigInt) -> bool;
+    fn challenge_from_transcript(&self, user: &str, y1: &BigInt, y2: &BigInt, r1: &BigInt, r2: &BigInt, nonce: &[u8]) -> BigInt;
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ChaumPedersenParameters {
     pub p: BigInt,
     pub q: BigInt,
     pub g: BigInt,
     pub h: BigInt,
     pub bit_size: u16,
+    pub group: GroupType,
+    pub seed: Vec<u8>,
+}
+
+impl ChaumPedersenParameters {
+    pub fn ristretto255() -> Self {
+        RISTRETTO255_PARAMETERS.clone()
+    }
+
+    pub fn with_generators(p: BigInt, q: BigInt, bit_size: u16, group: GroupType, seed: &[u8]) -> Self {
+        let mut parameters = ChaumPedersenParameters { p, q, g: BigInt::zero(), h: BigInt::zero(), bit_size, group, seed: seed.to_vec() };
+        parameters.g = parameters.derive_generator(b"g");
+        parameters.h = parameters.derive_generator(b"h");
+        parameters
+    }
+
+    // Checks that g and h are exactly the generators derived from the seed
+    pub fn verify_generators(&self) -> bool {
+        self.g == self.derive_generator(b"g") && self.h == self.derive_generator(b"h")
+    }
+
+    // Hashes the seed together with the group and a counter into the group, until the result is a generator
+    fn derive_generator(&self, name: &[u8]) -> BigInt {
+        let group = create_group(self);
+        let mut counter: u32 = 0;
+        loop {
+            let mut input = Vec::new();
+            for field in [GENERATOR_LABEL, self.group.name().as_bytes(), &self.p.to_bytes_be().1, &self.q.to_bytes_be().1, &self.seed, name, &counter.to_be_bytes()] {
+                append_field(&mut input, field);
+            }
+            if let Some(generator) = group.hash_to_element(&input) {
+                return generator;
+            }
+            counter += 1;
+        }
+    }
+}
+
+fn create_group(parameters: &ChaumPedersenParameters) -> Box<dyn Group> {
+    match parameters.group {
+        GroupType::ModP => Box::new(ModPGroup::new(&parameters.p, &parameters.q)),
+        GroupType::Ristretto255 => Box::new(Ristretto255Group::new(&parameters.q)),
+    }
+}
+
+// Fields are length-prefixed so that different inputs can not produce the same byte string
+pub(super) fn append_field(buffer: &mut Vec<u8>, field: &[u8]) {
+    buffer.extend_from_slice(&(field.len() as u64).to_be_bytes());
+    buffer.extend_from_slice(field);
 }
 
 #[derive(Debug)]
 pub struct ChaumPedersenAlgorthim {
     parameters: ChaumPedersenParameters,
-    rng: StdRng,
+    group: Box<dyn Group>,
 }
 
 impl ChaumPedersenAlgorthim {
-    pub fn new(parameters: &ChaumPedersenParameters) -> ChaumPedersenAlgorthim {
+    /// Fails if g or h is not an element of the group.
+    pub fn new(parameters: &ChaumPedersenParameters) -> Result<ChaumPedersenAlgorthim, String> {
         println!("Algorithm initialized with parameters: {:?}", parameters);
-        ChaumPedersenAlgorthim { parameters: parameters.clone(), rng: StdRng::from_entropy() }
+        let group = create_group(parameters);
+        if !group.is_element(&parameters.g) || !group.is_element(&parameters.h) {
+            return Err(String::from("The generators are not elements of the group"));
+        }
+        Ok(ChaumPedersenAlgorthim { parameters: parameters.clone(), group })
     }
 
-    pub fn find_parameters(bit_size: u16, fixed_parameters: bool) -> ChaumPedersenParameters {
+    // Fails if fixed parameters are requested for a bit size without fixed parameters
+    pub fn find_parameters(group: GroupType, bit_size: u16, fixed_parameters: bool) -> Result<ChaumPedersenParameters, String> {
+        if group == GroupType::Ristretto255 {
+            return Ok(ChaumPedersenParameters::ristretto255());
+        }
         if fixed_parameters {
-            let parameters = FIXED_PARAMETERS.read().unwrap();
-            return parameters.get(&bit_size).expect("No fixed parameters for this bit size defined").clone();
+            let parameters = FIXED_PARAMETERS.read().unwrap_or_else(PoisonError::into_inner);
+            return parameters.get(&bit_size).cloned().ok_or(format!("No fixed parameters for {} bits defined", bit_size));
+        }
+        // The generator does not set the most significant bit, so retry until p has the full bit size
+        let mut p = BigInt::zero();
+        while p.bits() < bit_size.into() {
+            p = BigInt::from_biguint(Sign::Plus, BigUint::from_bytes_be(&Generator::safe_prime(bit_size.into()).to_bytes_be()));
         }
-        let p = BigInt::from_biguint(Sign::Plus, BigUint::from_bytes_be(&Generator::safe_prime(bit_size.into()).to_bytes_be()));
         let q = (&p - 1.to_bigint().unwrap()) / 2.to_bigint().unwrap();
-        let g = 4.to_bigint().unwrap();
-        let h = 9.to_bigint().unwrap();
-        ChaumPedersenParameters { p, q, g, h, bit_size }
+        Ok(ChaumPedersenParameters::with_generators(p, q, bit_size, group, DEFAULT_GENERATOR_SEED))
     }
 }
 
@@ -65,32 +151,60 @@ impl ChaumPedersen for ChaumPedersenAlgorthim {
     }
 
     fn exponentiation(&self, x: &BigInt) -> (BigInt, BigInt) {
-        let y1 = self.parameters.g.modpow(x, &self.parameters.p);
-        let y2 = self.parameters.h.modpow(x, &self.parameters.p);
+        // new only accepts generators which are elements of the group, so the exponentiation can not fail
+        let y1 = self.group.exp(&self.parameters.g, x).expect("g is an element of the group");
+        let y2 = self.group.exp(&self.parameters.h, x).expect("h is an element of the group");
         (y1, y2)
     }
 
-    fn generate_random(&mut self) -> BigInt {
-        let mut c = 0.to_bigint().unwrap();
-        while c <= 1.to_bigint().unwrap() {
-            c = BigInt::from_biguint(Sign::Plus, self.rng.gen_biguint(self.parameters.bit_size.into()));
-        }
-        c
+    // Samples uniformly from [1, q), the range of all scalars.
+    // The thread-local generator is a reseeding CSPRNG, so concurrent calls need no lock.
+    fn generate_random(&self) -> BigInt {
+        rand::thread_rng().gen_bigint_range(&BigInt::one(), &self.parameters.q)
+    }
+
+    fn is_scalar(&self, value: &BigInt) -> bool {
+        value >= &BigInt::one() && value < &self.parameters.q
+    }
+
+    fn is_element(&self, value: &BigInt) -> bool {
+        self.group.is_element(value)
     }
 
     fn solve_challenge(&self, x: &BigInt, k: &BigInt, c: &BigInt) -> BigInt {
         let mut s = (k - (c * x)) % &self.parameters.q;
         if s < 0.to_bigint().unwrap() {
-            s = s + &self.parameters.q;
+            s += &self.parameters.q;
         }
         s
     }
 
     fn verify(&self, y1: &BigInt, y2: &BigInt, r1: &BigInt, r2: &BigInt, s: &BigInt, c: &BigInt) -> bool {
-        let v1 = (self.parameters.g.modpow(s, &self.parameters.p) * 
-            y1.modpow(c, &self.parameters.p)) % &self.parameters.p;
-        let v2 = (self.parameters.h.modpow(s, &self.parameters.p) * 
-            y2.modpow(c, &self.parameters.p)) % &self.parameters.p;
-        &v1 == r1 && &v2 == r2
+        if ![y1, y2, r1, r2].into_iter().all(|element| self.is_element(element)) || !self.is_scalar(s) || !self.is_scalar(c) {
+            return false;
+        }
+        let commitment = |base: &BigInt, y: &BigInt| -> Option<BigInt> {
+            self.group.combine(&self.group.exp(base, s)?, &self.group.exp(y, c)?)
+        };
+        match (commitment(&self.parameters.g, y1), commitment(&self.parameters.h, y2)) {
+            (Some(v1), Some(v2)) => &v1 == r1 && &v2 == r2,
+            _ => false
+        }
+    }
+
+    // Derives c non-interactively (Fiat-Shamir) by hashing everything the prover committed to
+    fn challenge_from_transcript(&self, user: &str, y1: &BigInt, y2: &BigInt, r1: &BigInt, r2: &BigInt, nonce: &[u8]) -> BigInt {
+        let mut transcript = Vec::new();
+        append_field(&mut transcript, FIAT_SHAMIR_LABEL);
+        append_field(&mut transcript, self.parameters.group.name().as_bytes());
+        for value in [&self.parameters.p, &self.parameters.q, &self.parameters.g, &self.parameters.h] {
+            append_field(&mut transcript, &value.to_bytes_be().1);
+        }
+        append_field(&mut transcript, user.as_bytes());
+        for value in [y1, y2, r1, r2] {
+            append_field(&mut transcript, &value.to_bytes_be().1);
+        }
+        append_field(&mut transcript, nonce);
+        BigInt::from_bytes_be(Sign::Plus, &Sha512::digest(&transcript)) % &self.parameters.q
     }
 }
\ No newline at end of file
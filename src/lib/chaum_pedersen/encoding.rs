@@ -0,0 +1,181 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use num_bigint::{BigInt, Sign};
+use serde::{Deserialize, Serialize};
+
+use super::algorithm::{append_field, ChaumPedersenParameters};
+use super::group::GroupType;
+
+const PEM_BEGIN: &str = "-----BEGIN CHAUM-PEDERSEN PARAMETERS-----";
+const PEM_END: &str = "-----END CHAUM-PEDERSEN PARAMETERS-----";
+const PEM_LINE_LENGTH: usize = 64;
+
+#[derive(Debug)]
+pub enum EncodingError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Pem(&'static str),
+    InvalidField(&'static str),
+}
+
+impl Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::Io(error) => write!(f, "Could not access the parameter file: {}", error),
+            EncodingError::Json(error) => write!(f, "Invalid JSON parameters: {}", error),
+            EncodingError::Pem(reason) => write!(f, "Invalid PEM parameters: {}", reason),
+            EncodingError::InvalidField(name) => write!(f, "Invalid parameter field {}", name),
+        }
+    }
+}
+
+impl Error for EncodingError {}
+
+impl From<std::io::Error> for EncodingError {
+    fn from(error: std::io::Error) -> Self {
+        EncodingError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for EncodingError {
+    fn from(error: serde_json::Error) -> Self {
+        EncodingError::Json(error)
+    }
+}
+
+// The JSON representation, numbers and the seed are hex encoded
+#[derive(Serialize, Deserialize)]
+struct EncodedParameters {
+    group: String,
+    bit_size: u16,
+    p: String,
+    q: String,
+    g: String,
+    h: String,
+    seed: String,
+}
+
+impl ChaumPedersenParameters {
+    pub fn to_json(&self) -> String {
+        let encoded = EncodedParameters {
+            group: self.group.name().to_string(),
+            bit_size: self.bit_size,
+            p: self.p.to_str_radix(16),
+            q: self.q.to_str_radix(16),
+            g: self.g.to_str_radix(16),
+            h: self.h.to_str_radix(16),
+            seed: hex::encode(&self.seed),
+        };
+        serde_json::to_string_pretty(&encoded).expect("Parameters can always be serialized")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, EncodingError> {
+        let encoded: EncodedParameters = serde_json::from_str(json)?;
+        Ok(ChaumPedersenParameters {
+            p: parse_hex(&encoded.p, "p")?,
+            q: parse_hex(&encoded.q, "q")?,
+            g: parse_hex(&encoded.g, "g")?,
+            h: parse_hex(&encoded.h, "h")?,
+            bit_size: encoded.bit_size,
+            group: encoded.group.parse::<GroupType>().map_err(|_| EncodingError::InvalidField("group"))?,
+            seed: hex::decode(&encoded.seed).map_err(|_| EncodingError::InvalidField("seed"))?,
+        })
+    }
+
+    // The PEM body is the base64 encoding of the length-prefixed fields group, bit_size, p, q, g, h and seed
+    pub fn to_pem(&self) -> String {
+        let mut encoded = Vec::new();
+        for field in [self.group.name().as_bytes(), &self.bit_size.to_be_bytes(), &self.p.to_bytes_be().1,
+                      &self.q.to_bytes_be().1, &self.g.to_bytes_be().1, &self.h.to_bytes_be().1, &self.seed] {
+            append_field(&mut encoded, field);
+        }
+        let body = STANDARD.encode(encoded);
+        let mut pem = String::from(PEM_BEGIN);
+        pem.push('\n');
+        for line in body.as_bytes().chunks(PEM_LINE_LENGTH) {
+            pem.push_str(std::str::from_utf8(line).expect("base64 is always valid UTF-8"));
+            pem.push('\n');
+        }
+        pem.push_str(PEM_END);
+        pem.push('\n');
+        pem
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self, EncodingError> {
+        let body = pem.trim()
+            .strip_prefix(PEM_BEGIN).ok_or(EncodingError::Pem("missing begin marker"))?
+            .strip_suffix(PEM_END).ok_or(EncodingError::Pem("missing end marker"))?;
+        let body: String = body.split_whitespace().collect();
+        let encoded = STANDARD.decode(body).map_err(|_| EncodingError::Pem("invalid base64"))?;
+        let mut fields = FieldReader { remaining: &encoded };
+        let group = std::str::from_utf8(fields.next()?).ok()
+            .and_then(|name| name.parse::<GroupType>().ok())
+            .ok_or(EncodingError::InvalidField("group"))?;
+        let bit_size = u16::from_be_bytes(fields.next()?.try_into().map_err(|_| EncodingError::InvalidField("bit_size"))?);
+        let parameters = ChaumPedersenParameters {
+            p: BigInt::from_bytes_be(Sign::Plus, fields.next()?),
+            q: BigInt::from_bytes_be(Sign::Plus, fields.next()?),
+            g: BigInt::from_bytes_be(Sign::Plus, fields.next()?),
+            h: BigInt::from_bytes_be(Sign::Plus, fields.next()?),
+            bit_size,
+            group,
+            seed: fields.next()?.to_vec(),
+        };
+        if !fields.remaining.is_empty() {
+            return Err(EncodingError::Pem("trailing data"));
+        }
+        Ok(parameters)
+    }
+
+    // Writes PEM if the file ends with .pem and JSON otherwise, fails with AlreadyExists instead of replacing an existing file
+    pub fn save(&self, path: &Path) -> Result<(), EncodingError> {
+        let content = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("pem") => self.to_pem(),
+            _ => self.to_json(),
+        };
+        fs::OpenOptions::new().write(true).create_new(true).open(path)?.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    // Detects the encoding from the content, so the file extension does not matter for reading
+    pub fn load(path: &Path) -> Result<Self, EncodingError> {
+        let content = fs::read_to_string(path)?;
+        if content.trim_start().starts_with(PEM_BEGIN) {
+            ChaumPedersenParameters::from_pem(&content)
+        } else {
+            ChaumPedersenParameters::from_json(&content)
+        }
+    }
+}
+
+fn parse_hex(value: &str, name: &'static str) -> Result<BigInt, EncodingError> {
+    BigInt::parse_bytes(value.as_bytes(), 16)
+        .filter(|number| number.sign() != Sign::Minus)
+        .ok_or(EncodingError::InvalidField(name))
+}
+
+struct FieldReader<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> FieldReader<'a> {
+    fn next(&mut self) -> Result<&'a [u8], EncodingError> {
+        if self.remaining.len() < 8 {
+            return Err(EncodingError::Pem("truncated field length"));
+        }
+        let (length, rest) = self.remaining.split_at(8);
+        let length = u64::from_be_bytes(length.try_into().expect("split at 8 bytes")) as usize;
+        if rest.len() < length {
+            return Err(EncodingError::Pem("truncated field"));
+        }
+        let (field, rest) = rest.split_at(length);
+        self.remaining = rest;
+        Ok(field)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,166 @@
+use std::fmt::{self, Debug, Display};
+use std::str::FromStr;
+
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use num_bigint::{BigInt, Sign};
+use num_traits::{One, Signed};
+use sha2::{Digest, Sha512};
+
+const RISTRETTO_ELEMENT_SIZE: usize = 32;
+// Extra bytes hashed beyond the size of p, so that reducing the hash mod p is not noticeably biased
+const REDUCTION_MARGIN: usize = 16;
+
+// The group in which the Chaum-Pedersen protocol is executed.
+// Group elements are always transported as BigInt so that the data access, logic and gRPC layers
+// do not need to know which group is in use. For elliptic curves this is the big-endian encoding
+// of the compressed point.
+pub trait Group: Debug + Send + Sync {
+    fn exp(&self, base: &BigInt, exponent: &BigInt) -> Option<BigInt>;
+    fn combine(&self, a: &BigInt, b: &BigInt) -> Option<BigInt>;
+    // Checks that the element is not the identity and belongs to the subgroup of prime order q
+    fn is_element(&self, element: &BigInt) -> bool;
+    // Maps the input to an element of the prime-order group whose discrete logarithm is unknown.
+    // Returns None for degenerate results, in which case the caller has to try another input.
+    fn hash_to_element(&self, input: &[u8]) -> Option<BigInt>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GroupType {
+    ModP,
+    Ristretto255,
+}
+
+impl GroupType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GroupType::ModP => "modp",
+            GroupType::Ristretto255 => "ristretto255",
+        }
+    }
+}
+
+impl Display for GroupType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for GroupType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "modp" => Ok(GroupType::ModP),
+            "ristretto255" => Ok(GroupType::Ristretto255),
+            _ => Err(format!("Unknown group '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ModPGroup {
+    p: BigInt,
+    q: BigInt,
+}
+
+impl ModPGroup {
+    pub fn new(p: &BigInt, q: &BigInt) -> Self {
+        Self { p: p.clone(), q: q.clone() }
+    }
+}
+
+impl Group for ModPGroup {
+    fn exp(&self, base: &BigInt, exponent: &BigInt) -> Option<BigInt> {
+        Some(base.modpow(exponent, &self.p))
+    }
+
+    fn combine(&self, a: &BigInt, b: &BigInt) -> Option<BigInt> {
+        Some((a * b) % &self.p)
+    }
+
+    // p - 1 is excluded by the subgroup check, because it has order 2
+    fn is_element(&self, element: &BigInt) -> bool {
+        element > &BigInt::one() && element < &self.p && element.modpow(&self.q, &self.p).is_one()
+    }
+
+    // Squaring maps the hash into the subgroup of quadratic residues, which has order q for a safe prime
+    fn hash_to_element(&self, input: &[u8]) -> Option<BigInt> {
+        let length = (self.p.bits() as usize).div_ceil(8) + REDUCTION_MARGIN;
+        let mut bytes = Vec::with_capacity(length);
+        let mut block: u32 = 0;
+        while bytes.len() < length {
+            bytes.extend_from_slice(&Sha512::new().chain_update(block.to_be_bytes()).chain_update(input).finalize());
+            block += 1;
+        }
+        bytes.truncate(length);
+        let element = (BigInt::from_bytes_be(Sign::Plus, &bytes) % &self.p).modpow(&BigInt::from(2), &self.p);
+        if element <= BigInt::one() {
+            return None;
+        }
+        Some(element)
+    }
+}
+
+#[derive(Debug)]
+pub struct Ristretto255Group {
+    order: BigInt,
+}
+
+impl Ristretto255Group {
+    pub fn new(order: &BigInt) -> Self {
+        Self { order: order.clone() }
+    }
+
+    pub fn encode(point: &RistrettoPoint) -> BigInt {
+        BigInt::from_bytes_be(Sign::Plus, point.compress().as_bytes())
+    }
+
+    pub fn decode(element: &BigInt) -> Option<RistrettoPoint> {
+        let (sign, bytes) = element.to_bytes_be();
+        if sign == Sign::Minus || bytes.len() > RISTRETTO_ELEMENT_SIZE {
+            return None;
+        }
+        let mut encoded = [0u8; RISTRETTO_ELEMENT_SIZE];
+        encoded[RISTRETTO_ELEMENT_SIZE - bytes.len()..].copy_from_slice(&bytes);
+        CompressedRistretto(encoded).decompress()
+    }
+
+    fn to_scalar(&self, value: &BigInt) -> Scalar {
+        let mut reduced = value % &self.order;
+        if reduced.is_negative() {
+            reduced += &self.order;
+        }
+        let (_, bytes) = reduced.to_bytes_le();
+        let mut encoded = [0u8; 32];
+        encoded[..bytes.len()].copy_from_slice(&bytes);
+        Scalar::from_bytes_mod_order(encoded)
+    }
+}
+
+impl Group for Ristretto255Group {
+    fn exp(&self, base: &BigInt, exponent: &BigInt) -> Option<BigInt> {
+        let point = Self::decode(base)?;
+        Some(Self::encode(&(point * self.to_scalar(exponent))))
+    }
+
+    fn combine(&self, a: &BigInt, b: &BigInt) -> Option<BigInt> {
+        Some(Self::encode(&(Self::decode(a)? + Self::decode(b)?)))
+    }
+
+    // Every decodable ristretto255 element besides the identity has order q
+    fn is_element(&self, element: &BigInt) -> bool {
+        matches!(Self::decode(element), Some(point) if point != RistrettoPoint::identity())
+    }
+
+    fn hash_to_element(&self, input: &[u8]) -> Option<BigInt> {
+        let mut uniform_bytes = [0u8; 64];
+        uniform_bytes.copy_from_slice(&Sha512::digest(input));
+        let point = RistrettoPoint::from_uniform_bytes(&uniform_bytes);
+        if point == RistrettoPoint::identity() {
+            return None;
+        }
+        Some(Self::encode(&point))
+    }
+}
\ No newline at end of file
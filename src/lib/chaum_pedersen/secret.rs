@@ -0,0 +1,57 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use num_bigint::{BigInt, Sign};
+use rand::RngCore;
+
+pub const SALT_LENGTH: usize = 16;
+// The weakest Argon2id costs accepted from clients, the OWASP baseline of 19 MiB, two passes and one lane
+pub const MIN_MEMORY_COST: u32 = 19 * 1024;
+pub const MIN_TIME_COST: u32 = 2;
+pub const MIN_PARALLELISM: u32 = 1;
+// The most expensive Argon2id costs accepted, so that neither side can make the other spend gigabytes or minutes on a login
+pub const MAX_MEMORY_COST: u32 = 256 * 1024;
+pub const MAX_TIME_COST: u32 = 16;
+pub const MAX_PARALLELISM: u32 = 16;
+
+// Extra output bytes beyond the size of q, so that reducing the hash mod q is not noticeably biased
+const REDUCTION_MARGIN: usize = 16;
+
+// The per-user parameters of the Argon2id key derivation which turns a password into the secret x
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecretParameters {
+    pub salt: Vec<u8>,
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+}
+
+impl SecretParameters {
+    // Generates a fresh random salt with the recommended Argon2id costs
+    pub fn generate() -> Self {
+        let mut salt = vec![0u8; SALT_LENGTH];
+        rand::thread_rng().fill_bytes(&mut salt);
+        Self {
+            salt,
+            memory_cost: Params::DEFAULT_M_COST,
+            time_cost: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.salt.len() >= SALT_LENGTH
+            && (MIN_MEMORY_COST..=MAX_MEMORY_COST).contains(&self.memory_cost)
+            && (MIN_TIME_COST..=MAX_TIME_COST).contains(&self.time_cost)
+            && (MIN_PARALLELISM..=MAX_PARALLELISM).contains(&self.parallelism)
+            && Params::new(self.memory_cost, self.time_cost, self.parallelism, None).is_ok()
+    }
+}
+
+// Derives the secret x from the password with Argon2id and maps it into [1, q)
+pub fn derive_secret(password: &str, parameters: &SecretParameters, q: &BigInt) -> Result<BigInt, argon2::Error> {
+    let output_length = (q.bits() as usize).div_ceil(8) + REDUCTION_MARGIN;
+    let params = Params::new(parameters.memory_cost, parameters.time_cost, parameters.parallelism, Some(output_length))?;
+    let mut output = vec![0u8; output_length];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), &parameters.salt, &mut output)?;
+    Ok(BigInt::from_bytes_be(Sign::Plus, &output) % (q - 1) + 1)
+}
\ No newline at end of file
@@ -0,0 +1,104 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::traits::Identity;
+use num_bigint::BigInt;
+use num_primes::Verification;
+use num_traits::One;
+
+use super::algorithm::ChaumPedersenParameters;
+use super::group::{GroupType, Ristretto255Group};
+use super::named_group::NamedGroup;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParameterError {
+    TooSmall { bits: u64, minimum: u64 },
+    NotPrime(&'static str),
+    NotSafePrime,
+    InvalidGenerator(&'static str),
+    GeneratorsNotFromSeed,
+    UnknownCurve,
+}
+
+impl Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterError::TooSmall { bits, minimum } => write!(f, "p has {} bits, but at least {} are required", bits, minimum),
+            ParameterError::NotPrime(name) => write!(f, "{} is not prime", name),
+            ParameterError::NotSafePrime => write!(f, "p is not the safe prime 2q + 1"),
+            ParameterError::InvalidGenerator(name) => write!(f, "{} does not generate the subgroup of order q", name),
+            ParameterError::GeneratorsNotFromSeed => write!(f, "g and h can not be derived from the seed"),
+            ParameterError::UnknownCurve => write!(f, "p and q do not belong to the ristretto255 group"),
+        }
+    }
+}
+
+impl Error for ParameterError {}
+
+impl ChaumPedersenParameters {
+    // Checks that the parameters describe a group of prime order q in which the protocol is sound.
+    // The minimum bit size only applies to the multiplicative group mod p.
+    pub fn validate(&self, minimum_bit_size: u16) -> Result<(), ParameterError> {
+        // The primes of the well-known groups are known to be safe, testing them again takes too long
+        self.validate_with(minimum_bit_size, NamedGroup::from_prime(&self.p).is_some())
+    }
+
+    // Runs the primality tests also for the well-known groups, which checks the transcribed constants
+    pub fn validate_fully(&self, minimum_bit_size: u16) -> Result<(), ParameterError> {
+        self.validate_with(minimum_bit_size, false)
+    }
+
+    fn validate_with(&self, minimum_bit_size: u16, well_known: bool) -> Result<(), ParameterError> {
+        match self.group {
+            GroupType::ModP => self.validate_modp(minimum_bit_size, well_known)?,
+            GroupType::Ristretto255 => self.validate_ristretto255()?,
+        }
+        if !self.verify_generators() {
+            return Err(ParameterError::GeneratorsNotFromSeed);
+        }
+        Ok(())
+    }
+
+    fn validate_modp(&self, minimum_bit_size: u16, well_known: bool) -> Result<(), ParameterError> {
+        if self.p.bits() < minimum_bit_size.into() {
+            return Err(ParameterError::TooSmall { bits: self.p.bits(), minimum: minimum_bit_size.into() });
+        }
+        if !well_known && !is_prime(&self.p) {
+            return Err(ParameterError::NotPrime("p"));
+        }
+        if !well_known && !is_prime(&self.q) {
+            return Err(ParameterError::NotPrime("q"));
+        }
+        if self.p != &self.q * 2 + 1 {
+            return Err(ParameterError::NotSafePrime);
+        }
+        // Since p = 2q + 1, every element besides 1 with x^q = 1 has order q
+        let one = BigInt::one();
+        for (name, generator) in [("g", &self.g), ("h", &self.h)] {
+            if generator <= &one || generator >= &(&self.p - 1) || generator.modpow(&self.q, &self.p) != one {
+                return Err(ParameterError::InvalidGenerator(name));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_ristretto255(&self) -> Result<(), ParameterError> {
+        let expected = ChaumPedersenParameters::ristretto255();
+        if self.p != expected.p || self.q != expected.q {
+            return Err(ParameterError::UnknownCurve);
+        }
+        // Every point of the ristretto255 group besides the identity has order q
+        for (name, generator) in [("g", &self.g), ("h", &self.h)] {
+            match Ristretto255Group::decode(generator) {
+                Some(point) if point != RistrettoPoint::identity() => {},
+                _ => return Err(ParameterError::InvalidGenerator(name)),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_prime(value: &BigInt) -> bool {
+    Verification::is_prime(&num_primes::BigUint::from_bytes_be(&value.to_bytes_be().1))
+}
\ No newline at end of file
@@ -1,3 +1,7 @@
 pub mod access;
 pub mod model;
-pub mod map_access;
\ No newline at end of file
+pub mod map_access;
+pub mod sqlite_access;
+pub mod postgres_access;
+pub mod redis_access;
+pub mod split_access;
\ No newline at end of file
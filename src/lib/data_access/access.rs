@@ -1,12 +1,176 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use async_trait::async_trait;
 use num_bigint::BigInt;
 
-use super::{model::User, model::Challenge};
+use crate::chaum_pedersen::secret::SecretParameters;
+
+use super::{model::User, model::Challenge, model::Session, model::Failures};
+
+#[derive(Debug)]
+pub enum StorageError {
+    // The store could not be reached or did not execute the query
+    Unavailable(String),
+    // A stored value could not be read
+    Corrupted(String),
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Unavailable(reason) => write!(f, "The storage is unavailable: {}", reason),
+            StorageError::Corrupted(reason) => write!(f, "The storage contains an invalid value: {}", reason),
+        }
+    }
+}
+
+impl Error for StorageError {}
+
+// Long-lived data which has to be kept until the user is deleted
+#[async_trait]
+pub trait UserAccess {
+    // Only inserts the user if the name is not taken yet and returns whether it was inserted
+    async fn create_user(&self, user_name: &str, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError>;
+    async fn get_user(&self, name: &str) -> Result<Option<User>, StorageError>;
+    // Only replaces the secret if it is still the one of the given user and returns whether it was replaced
+    async fn update_user_secret(&self, user: &User, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError>;
+    // Returns whether the user existed
+    async fn delete_user(&self, name: &str) -> Result<bool, StorageError>;
+    // Fails if the store can not be reached
+    async fn ping_users(&self) -> Result<(), StorageError>;
+}
+
+// Short-lived data which is only valid until it expires
+#[async_trait]
+pub trait ChallengeAccess {
+    // Removes the challenges of the user which expire first, so that at most `max_challenges` remain together with the new one.
+    // The insert and the removal are one step, so that concurrent requests of the user can not exceed the limit.
+    async fn create_auth_challenge(&self, challenge: &Challenge, max_challenges: usize) -> Result<(), StorageError>;
+    // Reads and deletes the challenge in one step, so that concurrent requests can not both consume it
+    async fn consume_auth_challenge(&self, auth_id: &str) -> Result<Option<Challenge>, StorageError>;
+    // Ends the sessions of the user which expire first in the same way as the challenges are removed
+    async fn create_session(&self, session: &Session, max_sessions: usize) -> Result<(), StorageError>;
+    // Reads and deletes the session in one step, so that it can only be refreshed or logged out once
+    async fn consume_session(&self, session_id: &str) -> Result<Option<Session>, StorageError>;
+    // Removes all challenges, sessions, used proofs and failure counters which expired at or before the given unix timestamp
+    async fn delete_expired(&self, now: u64) -> Result<(), StorageError>;
+    // Keeps the proof until it expires and returns whether it was new
+    async fn record_proof(&self, id: &str, expires_at: u64) -> Result<bool, StorageError>;
+    // Removes all challenges and sessions of the user
+    async fn delete_by_user(&self, user_id: &str) -> Result<(), StorageError>;
+    async fn get_challenge(&self, id: &str) -> Result<Option<Challenge>, StorageError>;
+    async fn get_session(&self, id: &str) -> Result<Option<Session>, StorageError>;
+    // May include expired sessions which were not removed yet
+    async fn get_user_sessions(&self, user_id: &str) -> Result<Vec<Session>, StorageError>;
+    // Increments the counter in one step and restarts it if it already expired
+    async fn record_failure(&self, id: &str, now: u64, expires_at: u64) -> Result<(), StorageError>;
+    async fn get_failures(&self, id: &str) -> Result<Option<Failures>, StorageError>;
+    async fn reset_failures(&self, id: &str) -> Result<(), StorageError>;
+    // Fails if the store can not be reached
+    async fn ping_challenges(&self) -> Result<(), StorageError>;
+}
+
+#[async_trait]
+pub trait DataAccess: UserAccess + ChallengeAccess {
+    // Fails if one of the stores can not be reached
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.ping_users().await?;
+        self.ping_challenges().await
+    }
+}
+
+impl<Access> DataAccess for Access
+where
+    Access: UserAccess + ChallengeAccess + ?Sized,
+{
+}
+
+// Allows to select the implementation at runtime
+#[async_trait]
+impl<Access> UserAccess for Box<Access>
+where
+    Access: UserAccess + Send + Sync + ?Sized,
+{
+    async fn create_user(&self, user_name: &str, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError> {
+        (**self).create_user(user_name, y1, y2, secret_parameters).await
+    }
+
+    async fn get_user(&self, name: &str) -> Result<Option<User>, StorageError> {
+        (**self).get_user(name).await
+    }
+
+    async fn update_user_secret(&self, user: &User, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError> {
+        (**self).update_user_secret(user, y1, y2, secret_parameters).await
+    }
+
+    async fn delete_user(&self, name: &str) -> Result<bool, StorageError> {
+        (**self).delete_user(name).await
+    }
+
+    async fn ping_users(&self) -> Result<(), StorageError> {
+        (**self).ping_users().await
+    }
+}
+
+#[async_trait]
+impl<Access> ChallengeAccess for Box<Access>
+where
+    Access: ChallengeAccess + Send + Sync + ?Sized,
+{
+    async fn create_auth_challenge(&self, challenge: &Challenge, max_challenges: usize) -> Result<(), StorageError> {
+        (**self).create_auth_challenge(challenge, max_challenges).await
+    }
+
+    async fn consume_auth_challenge(&self, auth_id: &str) -> Result<Option<Challenge>, StorageError> {
+        (**self).consume_auth_challenge(auth_id).await
+    }
+
+    async fn create_session(&self, session: &Session, max_sessions: usize) -> Result<(), StorageError> {
+        (**self).create_session(session, max_sessions).await
+    }
+
+    async fn consume_session(&self, session_id: &str) -> Result<Option<Session>, StorageError> {
+        (**self).consume_session(session_id).await
+    }
+
+    async fn delete_expired(&self, now: u64) -> Result<(), StorageError> {
+        (**self).delete_expired(now).await
+    }
+
+    async fn record_proof(&self, id: &str, expires_at: u64) -> Result<bool, StorageError> {
+        (**self).record_proof(id, expires_at).await
+    }
+
+    async fn delete_by_user(&self, user_id: &str) -> Result<(), StorageError> {
+        (**self).delete_by_user(user_id).await
+    }
+
+    async fn get_challenge(&self, id: &str) -> Result<Option<Challenge>, StorageError> {
+        (**self).get_challenge(id).await
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>, StorageError> {
+        (**self).get_session(id).await
+    }
+
+    async fn get_user_sessions(&self, user_id: &str) -> Result<Vec<Session>, StorageError> {
+        (**self).get_user_sessions(user_id).await
+    }
+
+    async fn record_failure(&self, id: &str, now: u64, expires_at: u64) -> Result<(), StorageError> {
+        (**self).record_failure(id, now, expires_at).await
+    }
+
+    async fn get_failures(&self, id: &str) -> Result<Option<Failures>, StorageError> {
+        (**self).get_failures(id).await
+    }
+
+    async fn reset_failures(&self, id: &str) -> Result<(), StorageError> {
+        (**self).reset_failures(id).await
+    }
 
-pub trait DataAccess {
-    fn create_user(&mut self, user_name: &String, y1: &BigInt, y2: &BigInt);
-    fn create_auth_challenge(&mut self, user_id: &String, auth_id: &String, c: &BigInt, r1: &BigInt, r2: &BigInt);
-    fn delete_auth_challenge(&mut self, auth_id: &String);
-    fn create_session(&mut self, user_id: &String, session_id: &String);
-    fn get_user(&self, name: &String) -> Option<&User>;
-    fn get_challenge(&self, id: &String) -> Option<&Challenge>;
+    async fn ping_challenges(&self) -> Result<(), StorageError> {
+        (**self).ping_challenges().await
+    }
 }
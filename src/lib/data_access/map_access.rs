@@ -1,68 +1,224 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::HashSet;
 
+use async_trait::async_trait;
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
 use num_bigint::BigInt;
 
-use super::access::DataAccess;
+use crate::chaum_pedersen::secret::SecretParameters;
+
+use super::access::{UserAccess, ChallengeAccess, StorageError};
 use super::model::User;
 use super::model::Challenge;
 use super::model::Session;
+use super::model::Failures;
 
 
+// The maps are sharded, so concurrent requests only contend if they access the same shard
 #[derive(Debug)]
 pub struct MapDataAccess{
-    users: HashMap<String, User>,
-    challenges: HashMap<String, Challenge>,
-    session: HashMap<String, Session>
+    users: DashMap<String, User>,
+    challenges: DashMap<String, Challenge>,
+    // The ids of the open challenges and sessions of every user
+    user_challenges: DashMap<String, HashSet<String>>,
+    session: DashMap<String, Session>,
+    user_sessions: DashMap<String, HashSet<String>>,
+    // The expiry of every used proof
+    proofs: DashMap<String, u64>,
+    failures: DashMap<String, Failures>
 }
 
 impl MapDataAccess {    
     pub fn new() -> Self {
         Self {
-            users: HashMap::new(),
-            challenges: HashMap::new(),
-            session: HashMap::new()
+            users: DashMap::new(),
+            challenges: DashMap::new(),
+            user_challenges: DashMap::new(),
+            session: DashMap::new(),
+            user_sessions: DashMap::new(),
+            proofs: DashMap::new(),
+            failures: DashMap::new()
         }
     }
+
+    fn remove_challenge(&self, auth_id: &str) -> Option<Challenge> {
+        let (_, challenge) = self.challenges.remove(auth_id)?;
+        remove_id(&self.user_challenges, &challenge.user_id, auth_id);
+        Some(challenge)
+    }
+
+    fn remove_session(&self, session_id: &str) -> Option<Session> {
+        let (_, session) = self.session.remove(session_id)?;
+        remove_id(&self.user_sessions, &session.user_id, session_id);
+        Some(session)
+    }
 }
 
-impl DataAccess for MapDataAccess {
-    fn create_user(&mut self, user_name: &String, y1: &BigInt, y2: &BigInt) {
-        self.users.insert(user_name.clone(), User{
-            id: user_name.clone(),
-            y1: y1.clone(),
-            y2: y2.clone(),
-            auth_id: None,
-            session_id: None
-        });
+fn remove_id(index: &DashMap<String, HashSet<String>>, user_id: &str, id: &str) {
+    index.remove_if_mut(user_id, |_, ids| {
+        ids.remove(id);
+        ids.is_empty()
+    });
+}
+
+// The entry of the user locks its shard of the index while the value is inserted and the others are trimmed,
+// so that concurrent requests of the user are applied one after the other. Ids whose value is already gone are dropped first.
+fn insert_trimmed<Value: Clone>(index: &DashMap<String, HashSet<String>>, values: &DashMap<String, Value>, user_id: &str, id: &str, value: &Value, max: usize, expires_at: impl Fn(&Value) -> u64) {
+    let mut ids = index.entry(user_id.to_string()).or_default();
+    let mut others: Vec<(String, u64)> = ids.iter()
+        .map(|other| (other.clone(), values.get(other).map_or(0, |value| expires_at(&value))))
+        .collect();
+    others.sort_by_key(|(_, expires_at)| Reverse(*expires_at));
+    for (evicted, _) in others.into_iter().skip(max.saturating_sub(1)) {
+        ids.remove(&evicted);
+        values.remove(&evicted);
+    }
+    values.insert(id.to_string(), value.clone());
+    ids.insert(id.to_string());
+}
+
+// Copies the ids first, so that the index is not locked while the values are read
+fn get_by_user<Value: Clone>(index: &DashMap<String, HashSet<String>>, values: &DashMap<String, Value>, user_id: &str) -> Vec<Value> {
+    let ids = index.get(user_id).map(|ids| ids.clone()).unwrap_or_default();
+    ids.iter().filter_map(|id| values.get(id).map(|value| value.clone())).collect()
+}
+
+#[async_trait]
+impl UserAccess for MapDataAccess {
+    // The entry locks its shard, so concurrent registrations of the same name can not both insert
+    async fn create_user(&self, user_name: &str, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError> {
+        match self.users.entry(user_name.to_string()) {
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(entry) => {
+                entry.insert(User{
+                    id: user_name.to_string(),
+                    y1: y1.clone(),
+                    y2: y2.clone(),
+                    secret_parameters: secret_parameters.clone()
+                });
+                Ok(true)
+            }
+        }
+    }
+
+    async fn get_user(&self, name: &str) -> Result<Option<User>, StorageError> {
+        Ok(self.users.get(name).map(|user| user.clone()))
+    }
+
+    // The reference locks its shard, so concurrent changes can not both replace the same secret
+    async fn update_user_secret(&self, user: &User, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError> {
+        match self.users.get_mut(&user.id) {
+            Some(mut stored) if stored.y1 == user.y1 && stored.y2 == user.y2 => {
+                stored.y1 = y1.clone();
+                stored.y2 = y2.clone();
+                stored.secret_parameters = secret_parameters.clone();
+                Ok(true)
+            },
+            _ => Ok(false)
+        }
+    }
+
+    async fn delete_user(&self, name: &str) -> Result<bool, StorageError> {
+        Ok(self.users.remove(name).is_some())
+    }
+
+    async fn ping_users(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChallengeAccess for MapDataAccess {
+    async fn create_auth_challenge(&self, challenge: &Challenge, max_challenges: usize) -> Result<(), StorageError> {
+        insert_trimmed(&self.user_challenges, &self.challenges, &challenge.user_id, &challenge.id, challenge, max_challenges, |challenge| challenge.expires_at);
+        Ok(())
+    }
+
+    async fn consume_auth_challenge(&self, auth_id: &str) -> Result<Option<Challenge>, StorageError> {
+        Ok(self.remove_challenge(auth_id))
+    }
+
+    async fn create_session(&self, session: &Session, max_sessions: usize) -> Result<(), StorageError> {
+        insert_trimmed(&self.user_sessions, &self.session, &session.user_id, &session.id, session, max_sessions, |session| session.expires_at);
+        Ok(())
     }
 
-    fn create_auth_challenge(&mut self, user_id: &String, auth_id: &String, c: &BigInt, r1: &BigInt, r2: &BigInt) {
-        self.users.get_mut(user_id).unwrap().auth_id = Some(auth_id.clone());
-        self.challenges.insert(auth_id.clone(), Challenge{
-            id: auth_id.clone(),
-            c: c.clone(),
-            r1: r1.clone(),
-            r2: r2.clone(),
-            user_id: user_id.clone()
+    async fn consume_session(&self, session_id: &str) -> Result<Option<Session>, StorageError> {
+        Ok(self.remove_session(session_id))
+    }
+
+    async fn delete_expired(&self, now: u64) -> Result<(), StorageError> {
+        self.challenges.retain(|_, challenge| challenge.expires_at > now);
+        self.session.retain(|_, session| session.expires_at > now);
+        self.proofs.retain(|_, expires_at| *expires_at > now);
+        self.user_challenges.retain(|_, auth_ids| {
+            auth_ids.retain(|auth_id| self.challenges.contains_key(auth_id));
+            !auth_ids.is_empty()
+        });
+        self.user_sessions.retain(|_, session_ids| {
+            session_ids.retain(|session_id| self.session.contains_key(session_id));
+            !session_ids.is_empty()
         });
+        self.failures.retain(|_, failures| failures.expires_at > now);
+        Ok(())
+    }
+
+    // The entry locks its shard, so the same proof can only be recorded once
+    async fn record_proof(&self, id: &str, expires_at: u64) -> Result<bool, StorageError> {
+        match self.proofs.entry(id.to_string()) {
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(entry) => {
+                entry.insert(expires_at);
+                Ok(true)
+            }
+        }
     }
 
-    fn delete_auth_challenge(&mut self, auth_id: &String) {
-        //TODO check if auth_id exists and user exists
-        let challenge = self.challenges.remove(auth_id);
-        self.users.get_mut(&challenge.unwrap().user_id).unwrap().auth_id = None;
+    async fn delete_by_user(&self, user_id: &str) -> Result<(), StorageError> {
+        for auth_id in self.user_challenges.remove(user_id).map(|(_, auth_ids)| auth_ids).unwrap_or_default() {
+            self.challenges.remove(&auth_id);
+        }
+        for session_id in self.user_sessions.remove(user_id).map(|(_, session_ids)| session_ids).unwrap_or_default() {
+            self.session.remove(&session_id);
+        }
+        Ok(())
+    }
+
+    async fn get_challenge(&self, id: &str) -> Result<Option<Challenge>, StorageError> {
+        Ok(self.challenges.get(id).map(|challenge| challenge.clone()))
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>, StorageError> {
+        Ok(self.session.get(id).map(|session| session.clone()))
     }
 
-    fn create_session(&mut self, user_name: &String, session_id: &String) {
-        self.users.get_mut(user_name).unwrap().session_id = Some(session_id.clone());
-        self.session.insert(session_id.clone(), Session { id: session_id.clone(), user_id: user_name.clone() });
+    async fn get_user_sessions(&self, user_id: &str) -> Result<Vec<Session>, StorageError> {
+        Ok(get_by_user(&self.user_sessions, &self.session, user_id))
     }
 
-    fn get_user(&self, name: &String) -> Option<&User> {
-        self.users.get(name)
+    // The entry locks its shard, so concurrent failures are all counted
+    async fn record_failure(&self, id: &str, now: u64, expires_at: u64) -> Result<(), StorageError> {
+        let mut failures = self.failures.entry(id.to_string()).or_insert(Failures { count: 0, last_failure: now, expires_at });
+        if failures.expires_at <= now {
+            failures.count = 0;
+        }
+        failures.count += 1;
+        failures.last_failure = now;
+        failures.expires_at = expires_at;
+        Ok(())
+    }
+
+    async fn get_failures(&self, id: &str) -> Result<Option<Failures>, StorageError> {
+        Ok(self.failures.get(id).map(|failures| failures.clone()))
     }
-    
-    fn get_challenge(&self, id: &String) -> Option<&Challenge> {
-        self.challenges.get(id)
+
+    async fn reset_failures(&self, id: &str) -> Result<(), StorageError> {
+        self.failures.remove(id);
+        Ok(())
+    }
+
+    async fn ping_challenges(&self) -> Result<(), StorageError> {
+        Ok(())
     }
 }
\ No newline at end of file
@@ -1,13 +1,14 @@
 use num_bigint::BigInt;
 
+use crate::chaum_pedersen::secret::SecretParameters;
+
 
 #[derive(Debug, Clone)]
 pub struct User {
     pub id: String,
     pub y1: BigInt,
     pub y2: BigInt,
-    pub auth_id: Option<String>,
-    pub session_id: Option<String>
+    pub secret_parameters: SecretParameters
 }
 
 #[derive(Debug, Clone)]
@@ -16,11 +17,21 @@ pub struct Challenge {
     pub c: BigInt,
     pub r1: BigInt,
     pub r2: BigInt,
-    pub user_id: String
+    pub user_id: String,
+    pub expires_at: u64
 }
 
 #[derive(Debug, Clone)]
 pub struct Session {
     pub id: String,
-    pub user_id: String
+    pub user_id: String,
+    pub expires_at: u64
+}
+
+// Failed attempts counted under a key, e.g. the user or the address of the client
+#[derive(Debug, Clone)]
+pub struct Failures {
+    pub count: u32,
+    pub last_failure: u64,
+    pub expires_at: u64
 }
\ No newline at end of file
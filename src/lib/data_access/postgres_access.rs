@@ -0,0 +1,272 @@
+use std::error::Error;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use deadpool_postgres::{Manager, ManagerConfig, Object, Pool, PoolError, RecyclingMethod};
+use num_bigint::{BigInt, Sign};
+use tokio::sync::OnceCell;
+use tokio_postgres::{NoTls, Row};
+
+use crate::chaum_pedersen::secret::SecretParameters;
+
+use super::access::{UserAccess, ChallengeAccess, StorageError};
+use super::model::{User, Challenge, Session, Failures};
+
+// Applied in order at startup, the applied versions are tracked in the schema_migrations table
+const MIGRATIONS: &[&str] = &[
+    include_str!("../../../migrations/postgres/0001_create_tables.sql"),
+    include_str!("../../../migrations/postgres/0002_drop_user_challenge_columns.sql"),
+    include_str!("../../../migrations/postgres/0003_create_sessions_user_id_index.sql"),
+    include_str!("../../../migrations/postgres/0004_create_failures.sql"),
+    include_str!("../../../migrations/postgres/0005_drop_challenges_user_foreign_key.sql"),
+];
+// Serializes the migrations of replicas which start at the same time
+const MIGRATION_LOCK: i64 = 0x7a6b_705f_6175_7468;
+
+async fn migrate(pool: &Pool) -> Result<(), StorageError> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    transaction.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK]).await?;
+    transaction.batch_execute("CREATE TABLE IF NOT EXISTS schema_migrations (version INTEGER PRIMARY KEY)").await?;
+    let version: i32 = transaction.query_one("SELECT COALESCE(MAX(version), 0) FROM schema_migrations", &[]).await?.get(0);
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        transaction.batch_execute(migration).await?;
+        transaction.execute("INSERT INTO schema_migrations (version) VALUES ($1)", &[&(index as i32 + 1)]).await?;
+    }
+    Ok(transaction.commit().await?)
+}
+
+// Connections are not encrypted, so the database has to be reached over a trusted network
+pub struct PostgresDataAccess {
+    pool: Pool,
+    migrated: OnceCell<()>,
+}
+
+impl PostgresDataAccess {
+    // Fails if the url is invalid, the connections are opened by the pool when they are needed
+    pub fn new(url: &str, pool_size: usize) -> Result<Self, Box<dyn Error>> {
+        let manager = Manager::from_config(tokio_postgres::Config::from_str(url)?, NoTls, ManagerConfig {
+            recycling_method: RecyclingMethod::Fast
+        });
+        let pool = Pool::builder(manager).max_size(pool_size.max(1)).build()?;
+        Ok(Self {
+            pool,
+            migrated: OnceCell::new()
+        })
+    }
+
+    // The schema is brought up to date by the first request, because the connections have to be driven by the runtime of the server
+    async fn client(&self) -> Result<Object, StorageError> {
+        self.migrated.get_or_try_init(|| migrate(&self.pool)).await?;
+        Ok(self.pool.get().await?)
+    }
+}
+
+fn to_bytes(value: &BigInt) -> Vec<u8> {
+    value.to_bytes_be().1
+}
+
+fn to_bigint(bytes: Vec<u8>) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, &bytes)
+}
+
+fn to_user(row: &Row) -> Result<User, tokio_postgres::Error> {
+    Ok(User {
+        id: row.try_get("id")?,
+        y1: to_bigint(row.try_get("y1")?),
+        y2: to_bigint(row.try_get("y2")?),
+        secret_parameters: SecretParameters {
+            salt: row.try_get("salt")?,
+            memory_cost: row.try_get::<_, i64>("memory_cost")? as u32,
+            time_cost: row.try_get::<_, i64>("time_cost")? as u32,
+            parallelism: row.try_get::<_, i64>("parallelism")? as u32
+        }
+    })
+}
+
+fn to_challenge(row: &Row) -> Result<Challenge, tokio_postgres::Error> {
+    Ok(Challenge {
+        id: row.try_get("id")?,
+        c: to_bigint(row.try_get("c")?),
+        r1: to_bigint(row.try_get("r1")?),
+        r2: to_bigint(row.try_get("r2")?),
+        user_id: row.try_get("user_id")?,
+        expires_at: row.try_get::<_, i64>("expires_at")? as u64
+    })
+}
+
+fn to_session(row: &Row) -> Result<Session, tokio_postgres::Error> {
+    Ok(Session {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        expires_at: row.try_get::<_, i64>("expires_at")? as u64
+    })
+}
+
+fn to_failures(row: &Row) -> Result<Failures, tokio_postgres::Error> {
+    Ok(Failures {
+        count: row.try_get::<_, i64>("count")? as u32,
+        last_failure: row.try_get::<_, i64>("last_failure")? as u64,
+        expires_at: row.try_get::<_, i64>("expires_at")? as u64
+    })
+}
+
+impl From<tokio_postgres::Error> for StorageError {
+    fn from(error: tokio_postgres::Error) -> Self {
+        StorageError::Unavailable(error.to_string())
+    }
+}
+
+impl From<PoolError> for StorageError {
+    fn from(error: PoolError) -> Self {
+        StorageError::Unavailable(error.to_string())
+    }
+}
+
+#[async_trait]
+impl UserAccess for PostgresDataAccess {
+    async fn create_user(&self, user_name: &str, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError> {
+        let inserted = self.client().await?.execute(
+            "INSERT INTO users (id, y1, y2, salt, memory_cost, time_cost, parallelism) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (id) DO NOTHING",
+            &[&user_name, &to_bytes(y1), &to_bytes(y2), &secret_parameters.salt, &i64::from(secret_parameters.memory_cost),
+              &i64::from(secret_parameters.time_cost), &i64::from(secret_parameters.parallelism)]
+        ).await?;
+        Ok(inserted == 1)
+    }
+
+    async fn get_user(&self, name: &str) -> Result<Option<User>, StorageError> {
+        let row = self.client().await?.query_opt("SELECT * FROM users WHERE id = $1", &[&name]).await?;
+        Ok(row.as_ref().map(to_user).transpose()?)
+    }
+
+    async fn update_user_secret(&self, user: &User, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError> {
+        let updated = self.client().await?.execute(
+            "UPDATE users SET y1 = $1, y2 = $2, salt = $3, memory_cost = $4, time_cost = $5, parallelism = $6 WHERE id = $7 AND y1 = $8 AND y2 = $9",
+            &[&to_bytes(y1), &to_bytes(y2), &secret_parameters.salt, &i64::from(secret_parameters.memory_cost), &i64::from(secret_parameters.time_cost),
+              &i64::from(secret_parameters.parallelism), &user.id, &to_bytes(&user.y1), &to_bytes(&user.y2)]
+        ).await?;
+        Ok(updated == 1)
+    }
+
+    // The sessions of the user are deleted with it by the foreign key. Migration 0005 drops the one of the challenges,
+    // because challenges are also stored for unknown users, so they are left to delete_by_user
+    async fn delete_user(&self, name: &str) -> Result<bool, StorageError> {
+        let deleted = self.client().await?.execute("DELETE FROM users WHERE id = $1", &[&name]).await?;
+        Ok(deleted == 1)
+    }
+
+    async fn ping_users(&self) -> Result<(), StorageError> {
+        self.client().await?.simple_query("SELECT 1").await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChallengeAccess for PostgresDataAccess {
+    // Concurrent transactions do not see each others inserts, so the ones of the same user are serialized by a lock on the user
+    async fn create_auth_challenge(&self, challenge: &Challenge, max_challenges: usize) -> Result<(), StorageError> {
+        let mut client = self.client().await?;
+        let transaction = client.transaction().await?;
+        transaction.execute("SELECT pg_advisory_xact_lock(hashtext('challenges:' || $1))", &[&challenge.user_id]).await?;
+        transaction.execute(
+            "INSERT INTO challenges (id, user_id, c, r1, r2, expires_at) VALUES ($1, $2, $3, $4, $5, $6)",
+            &[&challenge.id, &challenge.user_id, &to_bytes(&challenge.c), &to_bytes(&challenge.r1), &to_bytes(&challenge.r2), &(challenge.expires_at as i64)]
+        ).await?;
+        transaction.execute(
+            "DELETE FROM challenges WHERE id IN (SELECT id FROM challenges WHERE user_id = $1 AND id <> $2 ORDER BY expires_at DESC OFFSET $3)",
+            &[&challenge.user_id, &challenge.id, &(max_challenges.saturating_sub(1) as i64)]
+        ).await?;
+        Ok(transaction.commit().await?)
+    }
+
+    // DELETE ... RETURNING locks the row, so only one of several concurrent statements gets the challenge
+    async fn consume_auth_challenge(&self, auth_id: &str) -> Result<Option<Challenge>, StorageError> {
+        let challenge = self.client().await?.query_opt("DELETE FROM challenges WHERE id = $1 RETURNING *", &[&auth_id]).await?;
+        Ok(challenge.as_ref().map(to_challenge).transpose()?)
+    }
+
+    async fn create_session(&self, session: &Session, max_sessions: usize) -> Result<(), StorageError> {
+        let mut client = self.client().await?;
+        let transaction = client.transaction().await?;
+        transaction.execute("SELECT pg_advisory_xact_lock(hashtext('sessions:' || $1))", &[&session.user_id]).await?;
+        transaction.execute(
+            "INSERT INTO sessions (id, user_id, expires_at) VALUES ($1, $2, $3)",
+            &[&session.id, &session.user_id, &(session.expires_at as i64)]
+        ).await?;
+        transaction.execute(
+            "DELETE FROM sessions WHERE id IN (SELECT id FROM sessions WHERE user_id = $1 AND id <> $2 ORDER BY expires_at DESC OFFSET $3)",
+            &[&session.user_id, &session.id, &(max_sessions.saturating_sub(1) as i64)]
+        ).await?;
+        Ok(transaction.commit().await?)
+    }
+
+    async fn consume_session(&self, session_id: &str) -> Result<Option<Session>, StorageError> {
+        let session = self.client().await?.query_opt("DELETE FROM sessions WHERE id = $1 RETURNING *", &[&session_id]).await?;
+        Ok(session.as_ref().map(to_session).transpose()?)
+    }
+
+    async fn delete_expired(&self, now: u64) -> Result<(), StorageError> {
+        let now = now as i64;
+        let mut client = self.client().await?;
+        let transaction = client.transaction().await?;
+        transaction.execute("DELETE FROM challenges WHERE expires_at <= $1", &[&now]).await?;
+        transaction.execute("DELETE FROM sessions WHERE expires_at <= $1", &[&now]).await?;
+        transaction.execute("DELETE FROM proofs WHERE expires_at <= $1", &[&now]).await?;
+        transaction.execute("DELETE FROM failures WHERE expires_at <= $1", &[&now]).await?;
+        Ok(transaction.commit().await?)
+    }
+
+    async fn record_proof(&self, id: &str, expires_at: u64) -> Result<bool, StorageError> {
+        let inserted = self.client().await?.execute(
+            "INSERT INTO proofs (id, expires_at) VALUES ($1, $2) ON CONFLICT (id) DO NOTHING",
+            &[&id, &(expires_at as i64)]
+        ).await?;
+        Ok(inserted == 1)
+    }
+
+    async fn delete_by_user(&self, user_id: &str) -> Result<(), StorageError> {
+        let mut client = self.client().await?;
+        let transaction = client.transaction().await?;
+        transaction.execute("DELETE FROM challenges WHERE user_id = $1", &[&user_id]).await?;
+        transaction.execute("DELETE FROM sessions WHERE user_id = $1", &[&user_id]).await?;
+        Ok(transaction.commit().await?)
+    }
+
+    async fn get_challenge(&self, id: &str) -> Result<Option<Challenge>, StorageError> {
+        let row = self.client().await?.query_opt("SELECT * FROM challenges WHERE id = $1", &[&id]).await?;
+        Ok(row.as_ref().map(to_challenge).transpose()?)
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>, StorageError> {
+        let row = self.client().await?.query_opt("SELECT * FROM sessions WHERE id = $1", &[&id]).await?;
+        Ok(row.as_ref().map(to_session).transpose()?)
+    }
+
+    async fn get_user_sessions(&self, user_id: &str) -> Result<Vec<Session>, StorageError> {
+        let rows = self.client().await?.query("SELECT * FROM sessions WHERE user_id = $1", &[&user_id]).await?;
+        Ok(rows.iter().map(to_session).collect::<Result<_, _>>()?)
+    }
+
+    async fn record_failure(&self, id: &str, now: u64, expires_at: u64) -> Result<(), StorageError> {
+        self.client().await?.execute(
+            "INSERT INTO failures (id, count, last_failure, expires_at) VALUES ($1, 1, $2, $3) ON CONFLICT (id) DO UPDATE SET
+             count = CASE WHEN failures.expires_at <= $2 THEN 1 ELSE failures.count + 1 END, last_failure = $2, expires_at = $3",
+            &[&id, &(now as i64), &(expires_at as i64)]
+        ).await?;
+        Ok(())
+    }
+
+    async fn get_failures(&self, id: &str) -> Result<Option<Failures>, StorageError> {
+        let row = self.client().await?.query_opt("SELECT * FROM failures WHERE id = $1", &[&id]).await?;
+        Ok(row.as_ref().map(to_failures).transpose()?)
+    }
+
+    async fn reset_failures(&self, id: &str) -> Result<(), StorageError> {
+        self.client().await?.execute("DELETE FROM failures WHERE id = $1", &[&id]).await?;
+        Ok(())
+    }
+
+    async fn ping_challenges(&self) -> Result<(), StorageError> {
+        self.ping_users().await
+    }
+}
\ No newline at end of file
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use num_bigint::{BigInt, Sign};
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Client, RedisError, RedisResult};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+
+use super::access::{ChallengeAccess, StorageError};
+use super::model::{Challenge, Session, Failures};
+
+const CHALLENGE_PREFIX: &str = "auth:challenge:";
+const SESSION_PREFIX: &str = "auth:session:";
+// Sorted sets of the challenge and session ids of a user scored by their expiry, so that they can be listed and removed with the user
+const USER_CHALLENGES_PREFIX: &str = "auth:user_challenges:";
+const USER_SESSIONS_PREFIX: &str = "auth:user_sessions:";
+// Marker of a used proof which expires with it
+const PROOF_PREFIX: &str = "auth:proof:";
+// Hash with the count, the last failure and the expiry of a failure counter
+const FAILURES_PREFIX: &str = "auth:failures:";
+
+// The stored values, numbers are hex encoded
+#[derive(Serialize, Deserialize)]
+struct StoredChallenge {
+    user_id: String,
+    c: String,
+    r1: String,
+    r2: String,
+    expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredSession {
+    user_id: String,
+    expires_at: u64,
+}
+
+// Keeps challenges and sessions in Redis, which removes them by itself when they expire
+pub struct RedisChallengeAccess {
+    client: Client,
+    connection: OnceCell<ConnectionManager>,
+}
+
+impl RedisChallengeAccess {
+    // Fails if the url is invalid, the server is only connected by the first request, so the constructor does not block the runtime
+    pub fn new(url: &str) -> RedisResult<Self> {
+        let client = Client::open(url)?;
+        Ok(Self {
+            client,
+            connection: OnceCell::new()
+        })
+    }
+
+    // The connection is opened by the first request, because it has to be driven by the runtime of the server.
+    // It reconnects by itself if the connection breaks.
+    async fn connection(&self) -> Result<ConnectionManager, StorageError> {
+        let connection = self.connection.get_or_try_init(|| ConnectionManager::new(self.client.clone())).await?;
+        Ok(connection.clone())
+    }
+
+    // Returns the ids in the index of the user with their values and removes the ids whose values already expired
+    async fn get_by_user(&self, index_prefix: &str, prefix: &str, user_id: &str) -> Result<Vec<(String, String)>, StorageError> {
+        let mut connection = self.connection().await?;
+        let index = format!("{}{}", index_prefix, user_id);
+        let ids: Vec<String> = connection.zrange(&index, 0, -1).await?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys: Vec<String> = ids.iter().map(|id| format!("{}{}", prefix, id)).collect();
+        let values: Vec<Option<String>> = redis::cmd("MGET").arg(keys).query_async(&mut connection).await?;
+
+        let (stored, expired): (Vec<_>, Vec<_>) = ids.into_iter().zip(values).partition(|(_, value)| value.is_some());
+        if !expired.is_empty() {
+            let expired: Vec<String> = expired.into_iter().map(|(id, _)| id).collect();
+            connection.zrem::<_, _, ()>(&index, expired).await?;
+        }
+        Ok(stored.into_iter().filter_map(|(id, value)| value.map(|value| (id, value))).collect())
+    }
+
+    // Trims the index of the user to the `max - 1` ids which expire last and adds the new id in one transaction,
+    // so that concurrent requests of the user can not keep more than `max` of them. The values of the trimmed ids
+    // are deleted right after the transaction, if that fails they can still be read by their id until they expire.
+    async fn insert_trimmed(&self, index: &str, prefix: &str, id: &str, value: String, expires_at: u64, max: usize) -> Result<(), StorageError> {
+        let mut connection = self.connection().await?;
+        let last_trimmed = -(max.max(1) as i64);
+        // All values of an index live equally long, so the newest one expires last and the index can expire with it
+        let (trimmed,): (Vec<String>,) = redis::pipe().atomic()
+            .cmd("ZRANGE").arg(index).arg(0).arg(last_trimmed)
+            .cmd("ZREMRANGEBYRANK").arg(index).arg(0).arg(last_trimmed).ignore()
+            .cmd("SET").arg(format!("{}{}", prefix, id)).arg(value).arg("EXAT").arg(expires_at).ignore()
+            .cmd("ZADD").arg(index).arg(expires_at).arg(id).ignore()
+            .cmd("EXPIREAT").arg(index).arg(expires_at).ignore()
+            .query_async(&mut connection)
+            .await?;
+        if !trimmed.is_empty() {
+            let keys: Vec<String> = trimmed.iter().map(|id| format!("{}{}", prefix, id)).collect();
+            connection.del::<_, ()>(keys).await?;
+        }
+        Ok(())
+    }
+}
+
+impl From<RedisError> for StorageError {
+    fn from(error: RedisError) -> Self {
+        StorageError::Unavailable(error.to_string())
+    }
+}
+
+fn to_hex(value: &BigInt) -> String {
+    hex::encode(value.to_bytes_be().1)
+}
+
+fn to_bigint(value: &str) -> Result<BigInt, StorageError> {
+    let bytes = hex::decode(value).map_err(|error| StorageError::Corrupted(error.to_string()))?;
+    Ok(BigInt::from_bytes_be(Sign::Plus, &bytes))
+}
+
+fn to_challenge(id: &str, value: &str) -> Result<Challenge, StorageError> {
+    let stored: StoredChallenge = serde_json::from_str(value).map_err(|error| StorageError::Corrupted(error.to_string()))?;
+    Ok(Challenge {
+        id: id.to_string(),
+        c: to_bigint(&stored.c)?,
+        r1: to_bigint(&stored.r1)?,
+        r2: to_bigint(&stored.r2)?,
+        user_id: stored.user_id,
+        expires_at: stored.expires_at
+    })
+}
+
+fn to_session(id: &str, value: &str) -> Result<Session, StorageError> {
+    let stored: StoredSession = serde_json::from_str(value).map_err(|error| StorageError::Corrupted(error.to_string()))?;
+    Ok(Session {
+        id: id.to_string(),
+        user_id: stored.user_id,
+        expires_at: stored.expires_at
+    })
+}
+
+fn to_failures(id: &str, values: &HashMap<String, u64>) -> Result<Failures, StorageError> {
+    let value = |field: &str| values.get(field).copied().ok_or_else(|| StorageError::Corrupted(format!("The failures of {} have no {}", id, field)));
+    Ok(Failures {
+        count: value("count")? as u32,
+        last_failure: value("last_failure")?,
+        expires_at: value("expires_at")?
+    })
+}
+
+#[async_trait]
+impl ChallengeAccess for RedisChallengeAccess {
+    async fn create_auth_challenge(&self, challenge: &Challenge, max_challenges: usize) -> Result<(), StorageError> {
+        let value = serde_json::to_string(&StoredChallenge {
+            user_id: challenge.user_id.clone(),
+            c: to_hex(&challenge.c),
+            r1: to_hex(&challenge.r1),
+            r2: to_hex(&challenge.r2),
+            expires_at: challenge.expires_at
+        }).expect("Strings and numbers can always be serialized");
+        let user_challenges = format!("{}{}", USER_CHALLENGES_PREFIX, challenge.user_id);
+        self.insert_trimmed(&user_challenges, CHALLENGE_PREFIX, &challenge.id, value, challenge.expires_at, max_challenges).await
+    }
+
+    // GETDEL is atomic, so only one of several concurrent requests gets the challenge
+    async fn consume_auth_challenge(&self, auth_id: &str) -> Result<Option<Challenge>, StorageError> {
+        let mut connection = self.connection().await?;
+        let value: Option<String> = connection.get_del(format!("{}{}", CHALLENGE_PREFIX, auth_id)).await?;
+        let challenge = value.map(|value| to_challenge(auth_id, &value)).transpose()?;
+        if let Some(challenge) = &challenge {
+            connection.zrem::<_, _, ()>(format!("{}{}", USER_CHALLENGES_PREFIX, challenge.user_id), auth_id).await?;
+        }
+        Ok(challenge)
+    }
+
+    async fn create_session(&self, session: &Session, max_sessions: usize) -> Result<(), StorageError> {
+        let value = serde_json::to_string(&StoredSession {
+            user_id: session.user_id.clone(),
+            expires_at: session.expires_at
+        }).expect("Strings and numbers can always be serialized");
+        let user_sessions = format!("{}{}", USER_SESSIONS_PREFIX, session.user_id);
+        self.insert_trimmed(&user_sessions, SESSION_PREFIX, &session.id, value, session.expires_at, max_sessions).await
+    }
+
+    async fn consume_session(&self, session_id: &str) -> Result<Option<Session>, StorageError> {
+        let mut connection = self.connection().await?;
+        let value: Option<String> = connection.get_del(format!("{}{}", SESSION_PREFIX, session_id)).await?;
+        let session = value.map(|value| to_session(session_id, &value)).transpose()?;
+        if let Some(session) = &session {
+            connection.zrem::<_, _, ()>(format!("{}{}", USER_SESSIONS_PREFIX, session.user_id), session_id).await?;
+        }
+        Ok(session)
+    }
+
+    // Redis removes expired keys by itself
+    async fn delete_expired(&self, _now: u64) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    // Only sets the marker if it does not exist yet, so concurrent requests can not both record the proof
+    async fn record_proof(&self, id: &str, expires_at: u64) -> Result<bool, StorageError> {
+        let recorded: Option<String> = redis::cmd("SET").arg(format!("{}{}", PROOF_PREFIX, id)).arg(1).arg("NX").arg("EXAT").arg(expires_at)
+            .query_async(&mut self.connection().await?)
+            .await?;
+        Ok(recorded.is_some())
+    }
+
+    async fn delete_by_user(&self, user_id: &str) -> Result<(), StorageError> {
+        let mut connection = self.connection().await?;
+        let user_challenges = format!("{}{}", USER_CHALLENGES_PREFIX, user_id);
+        let user_sessions = format!("{}{}", USER_SESSIONS_PREFIX, user_id);
+        let auth_ids: Vec<String> = connection.zrange(&user_challenges, 0, -1).await?;
+        let session_ids: Vec<String> = connection.zrange(&user_sessions, 0, -1).await?;
+
+        let mut keys: Vec<String> = session_ids.iter().map(|session_id| format!("{}{}", SESSION_PREFIX, session_id)).collect();
+        keys.extend(auth_ids.iter().map(|auth_id| format!("{}{}", CHALLENGE_PREFIX, auth_id)));
+        keys.push(user_challenges);
+        keys.push(user_sessions);
+        connection.del::<_, ()>(keys).await?;
+        Ok(())
+    }
+
+    async fn get_challenge(&self, id: &str) -> Result<Option<Challenge>, StorageError> {
+        let value: Option<String> = self.connection().await?.get(format!("{}{}", CHALLENGE_PREFIX, id)).await?;
+        value.map(|value| to_challenge(id, &value)).transpose()
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>, StorageError> {
+        let value: Option<String> = self.connection().await?.get(format!("{}{}", SESSION_PREFIX, id)).await?;
+        value.map(|value| to_session(id, &value)).transpose()
+    }
+
+    async fn get_user_sessions(&self, user_id: &str) -> Result<Vec<Session>, StorageError> {
+        let sessions = self.get_by_user(USER_SESSIONS_PREFIX, SESSION_PREFIX, user_id).await?;
+        sessions.iter().map(|(id, value)| to_session(id, value)).collect()
+    }
+
+    // Redis removes the counter when it expires, so the increment starts a new one
+    async fn record_failure(&self, id: &str, now: u64, expires_at: u64) -> Result<(), StorageError> {
+        let key = format!("{}{}", FAILURES_PREFIX, id);
+        redis::pipe().atomic()
+            .cmd("HINCRBY").arg(&key).arg("count").arg(1).ignore()
+            .cmd("HSET").arg(&key).arg("last_failure").arg(now).arg("expires_at").arg(expires_at).ignore()
+            .cmd("EXPIREAT").arg(&key).arg(expires_at).ignore()
+            .query_async::<_, ()>(&mut self.connection().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_failures(&self, id: &str) -> Result<Option<Failures>, StorageError> {
+        let values: HashMap<String, u64> = self.connection().await?.hgetall(format!("{}{}", FAILURES_PREFIX, id)).await?;
+        if values.is_empty() {
+            return Ok(None);
+        }
+        to_failures(id, &values).map(Some)
+    }
+
+    async fn reset_failures(&self, id: &str) -> Result<(), StorageError> {
+        self.connection().await?.del::<_, ()>(format!("{}{}", FAILURES_PREFIX, id)).await?;
+        Ok(())
+    }
+
+    async fn ping_challenges(&self) -> Result<(), StorageError> {
+        redis::cmd("PING").query_async::<_, ()>(&mut self.connection().await?).await?;
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,116 @@
+use async_trait::async_trait;
+use num_bigint::BigInt;
+
+use crate::chaum_pedersen::secret::SecretParameters;
+
+use super::access::{UserAccess, ChallengeAccess, StorageError};
+use super::model::{User, Challenge, Session, Failures};
+
+// Keeps the users in one store and the challenges and sessions in another
+pub struct SplitDataAccess<Users, Challenges> {
+    users: Users,
+    challenges: Challenges,
+}
+
+impl<Users, Challenges> SplitDataAccess<Users, Challenges>
+where
+    Users: UserAccess,
+    Challenges: ChallengeAccess,
+{
+    pub fn new(users: Users, challenges: Challenges) -> Self {
+        Self {
+            users,
+            challenges
+        }
+    }
+}
+
+#[async_trait]
+impl<Users, Challenges> UserAccess for SplitDataAccess<Users, Challenges>
+where
+    Users: UserAccess + Send + Sync,
+    Challenges: Send + Sync,
+{
+    async fn create_user(&self, user_name: &str, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError> {
+        self.users.create_user(user_name, y1, y2, secret_parameters).await
+    }
+
+    async fn get_user(&self, name: &str) -> Result<Option<User>, StorageError> {
+        self.users.get_user(name).await
+    }
+
+    async fn update_user_secret(&self, user: &User, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError> {
+        self.users.update_user_secret(user, y1, y2, secret_parameters).await
+    }
+
+    async fn delete_user(&self, name: &str) -> Result<bool, StorageError> {
+        self.users.delete_user(name).await
+    }
+
+    async fn ping_users(&self) -> Result<(), StorageError> {
+        self.users.ping_users().await
+    }
+}
+
+#[async_trait]
+impl<Users, Challenges> ChallengeAccess for SplitDataAccess<Users, Challenges>
+where
+    Users: Send + Sync,
+    Challenges: ChallengeAccess + Send + Sync,
+{
+    async fn create_auth_challenge(&self, challenge: &Challenge, max_challenges: usize) -> Result<(), StorageError> {
+        self.challenges.create_auth_challenge(challenge, max_challenges).await
+    }
+
+    async fn consume_auth_challenge(&self, auth_id: &str) -> Result<Option<Challenge>, StorageError> {
+        self.challenges.consume_auth_challenge(auth_id).await
+    }
+
+    async fn create_session(&self, session: &Session, max_sessions: usize) -> Result<(), StorageError> {
+        self.challenges.create_session(session, max_sessions).await
+    }
+
+    async fn consume_session(&self, session_id: &str) -> Result<Option<Session>, StorageError> {
+        self.challenges.consume_session(session_id).await
+    }
+
+    async fn delete_expired(&self, now: u64) -> Result<(), StorageError> {
+        self.challenges.delete_expired(now).await
+    }
+
+    async fn record_proof(&self, id: &str, expires_at: u64) -> Result<bool, StorageError> {
+        self.challenges.record_proof(id, expires_at).await
+    }
+
+    async fn delete_by_user(&self, user_id: &str) -> Result<(), StorageError> {
+        self.challenges.delete_by_user(user_id).await
+    }
+
+    async fn get_challenge(&self, id: &str) -> Result<Option<Challenge>, StorageError> {
+        self.challenges.get_challenge(id).await
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>, StorageError> {
+        self.challenges.get_session(id).await
+    }
+
+    async fn get_user_sessions(&self, user_id: &str) -> Result<Vec<Session>, StorageError> {
+        self.challenges.get_user_sessions(user_id).await
+    }
+
+    async fn record_failure(&self, id: &str, now: u64, expires_at: u64) -> Result<(), StorageError> {
+        self.challenges.record_failure(id, now, expires_at).await
+    }
+
+    async fn get_failures(&self, id: &str) -> Result<Option<Failures>, StorageError> {
+        self.challenges.get_failures(id).await
+    }
+
+    async fn reset_failures(&self, id: &str) -> Result<(), StorageError> {
+        self.challenges.reset_failures(id).await
+    }
+
+    async fn ping_challenges(&self) -> Result<(), StorageError> {
+        self.challenges.ping_challenges().await
+    }
+}
\ No newline at end of file
@@ -0,0 +1,301 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use async_trait::async_trait;
+use num_bigint::{BigInt, Sign};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::chaum_pedersen::secret::SecretParameters;
+
+use super::access::{UserAccess, ChallengeAccess, StorageError};
+use super::model::{User, Challenge, Session, Failures};
+
+// Applied in order at startup, the number of applied migrations is tracked in the user_version pragma
+const MIGRATIONS: &[&str] = &[
+    include_str!("../../../migrations/sqlite/0001_create_tables.sql"),
+    include_str!("../../../migrations/sqlite/0002_drop_user_challenge_columns.sql"),
+    include_str!("../../../migrations/sqlite/0003_create_sessions_user_id_index.sql"),
+    include_str!("../../../migrations/sqlite/0004_create_failures.sql"),
+    include_str!("../../../migrations/sqlite/0005_drop_challenges_user_foreign_key.sql"),
+];
+
+#[derive(Debug)]
+pub struct SqliteDataAccess {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteDataAccess {
+    // Opens or creates the database at the path and brings its schema up to date
+    pub fn new(path: &str) -> Result<Self, rusqlite::Error> {
+        let mut connection = Connection::open(path)?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection))
+        })
+    }
+
+    // SQLite blocks while it accesses the file, so the queries run on the blocking thread pool of tokio
+    async fn execute<T, F>(&self, query: F) -> Result<T, StorageError>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, rusqlite::Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        let connection = self.connection.clone();
+        // A panicking query does not break the connection, so a poisoned lock is taken over instead of failing every later query
+        tokio::task::spawn_blocking(move || query(&mut connection.lock().unwrap_or_else(PoisonError::into_inner)))
+            .await
+            .map_err(|error| StorageError::Unavailable(error.to_string()))?
+            .map_err(StorageError::from)
+    }
+}
+
+fn migrate(connection: &mut Connection) -> Result<(), rusqlite::Error> {
+    let version: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+fn to_bytes(value: &BigInt) -> Vec<u8> {
+    value.to_bytes_be().1
+}
+
+fn to_bigint(bytes: Vec<u8>) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, &bytes)
+}
+
+fn to_user(row: &Row) -> Result<User, rusqlite::Error> {
+    Ok(User {
+        id: row.get("id")?,
+        y1: to_bigint(row.get("y1")?),
+        y2: to_bigint(row.get("y2")?),
+        secret_parameters: SecretParameters {
+            salt: row.get("salt")?,
+            memory_cost: row.get("memory_cost")?,
+            time_cost: row.get("time_cost")?,
+            parallelism: row.get("parallelism")?
+        }
+    })
+}
+
+fn to_challenge(row: &Row) -> Result<Challenge, rusqlite::Error> {
+    Ok(Challenge {
+        id: row.get("id")?,
+        c: to_bigint(row.get("c")?),
+        r1: to_bigint(row.get("r1")?),
+        r2: to_bigint(row.get("r2")?),
+        user_id: row.get("user_id")?,
+        expires_at: row.get::<_, i64>("expires_at")? as u64
+    })
+}
+
+fn to_session(row: &Row) -> Result<Session, rusqlite::Error> {
+    Ok(Session {
+        id: row.get("id")?,
+        user_id: row.get("user_id")?,
+        expires_at: row.get::<_, i64>("expires_at")? as u64
+    })
+}
+
+fn to_failures(row: &Row) -> Result<Failures, rusqlite::Error> {
+    Ok(Failures {
+        count: row.get("count")?,
+        last_failure: row.get::<_, i64>("last_failure")? as u64,
+        expires_at: row.get::<_, i64>("expires_at")? as u64
+    })
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(error: rusqlite::Error) -> Self {
+        match error {
+            rusqlite::Error::FromSqlConversionFailure(..) | rusqlite::Error::InvalidColumnType(..) => StorageError::Corrupted(error.to_string()),
+            _ => StorageError::Unavailable(error.to_string())
+        }
+    }
+}
+
+#[async_trait]
+impl UserAccess for SqliteDataAccess {
+    async fn create_user(&self, user_name: &str, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError> {
+        let (user_name, y1, y2, secret_parameters) = (user_name.to_string(), to_bytes(y1), to_bytes(y2), secret_parameters.clone());
+        self.execute(move |connection| {
+            let inserted = connection.execute(
+                "INSERT INTO users (id, y1, y2, salt, memory_cost, time_cost, parallelism) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7) ON CONFLICT (id) DO NOTHING",
+                params![user_name, y1, y2, secret_parameters.salt, secret_parameters.memory_cost, secret_parameters.time_cost, secret_parameters.parallelism]
+            )?;
+            Ok(inserted == 1)
+        }).await
+    }
+
+    async fn get_user(&self, name: &str) -> Result<Option<User>, StorageError> {
+        let name = name.to_string();
+        self.execute(move |connection| {
+            connection.query_row("SELECT * FROM users WHERE id = ?1", params![name], to_user).optional()
+        }).await
+    }
+
+    async fn update_user_secret(&self, user: &User, y1: &BigInt, y2: &BigInt, secret_parameters: &SecretParameters) -> Result<bool, StorageError> {
+        let (user_name, old_y1, old_y2) = (user.id.clone(), to_bytes(&user.y1), to_bytes(&user.y2));
+        let (y1, y2, secret_parameters) = (to_bytes(y1), to_bytes(y2), secret_parameters.clone());
+        self.execute(move |connection| {
+            let updated = connection.execute(
+                "UPDATE users SET y1 = ?1, y2 = ?2, salt = ?3, memory_cost = ?4, time_cost = ?5, parallelism = ?6 WHERE id = ?7 AND y1 = ?8 AND y2 = ?9",
+                params![y1, y2, secret_parameters.salt, secret_parameters.memory_cost, secret_parameters.time_cost, secret_parameters.parallelism, user_name, old_y1, old_y2]
+            )?;
+            Ok(updated == 1)
+        }).await
+    }
+
+    // The sessions of the user are deleted with it by the foreign key. Migration 0005 drops the one of the challenges,
+    // because challenges are also stored for unknown users, so they are left to delete_by_user
+    async fn delete_user(&self, name: &str) -> Result<bool, StorageError> {
+        let name = name.to_string();
+        self.execute(move |connection| {
+            let deleted = connection.execute("DELETE FROM users WHERE id = ?1", params![name])?;
+            Ok(deleted == 1)
+        }).await
+    }
+
+    async fn ping_users(&self) -> Result<(), StorageError> {
+        self.execute(|connection| connection.query_row("SELECT 1", [], |_| Ok(()))).await
+    }
+}
+
+#[async_trait]
+impl ChallengeAccess for SqliteDataAccess {
+    // The insert takes the write lock of the database, so the trimming of concurrent transactions sees the new challenge
+    async fn create_auth_challenge(&self, challenge: &Challenge, max_challenges: usize) -> Result<(), StorageError> {
+        let (user_id, auth_id, c, r1, r2) = (challenge.user_id.clone(), challenge.id.clone(), to_bytes(&challenge.c), to_bytes(&challenge.r1), to_bytes(&challenge.r2));
+        let expires_at = challenge.expires_at;
+        self.execute(move |connection| {
+            let transaction = connection.transaction()?;
+            transaction.execute(
+                "INSERT INTO challenges (id, user_id, c, r1, r2, expires_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![auth_id, user_id, c, r1, r2, expires_at as i64]
+            )?;
+            transaction.execute(
+                "DELETE FROM challenges WHERE id IN (SELECT id FROM challenges WHERE user_id = ?1 AND id <> ?2 ORDER BY expires_at DESC LIMIT -1 OFFSET ?3)",
+                params![user_id, auth_id, max_challenges.saturating_sub(1) as i64]
+            )?;
+            transaction.commit()
+        }).await
+    }
+
+    async fn consume_auth_challenge(&self, auth_id: &str) -> Result<Option<Challenge>, StorageError> {
+        let auth_id = auth_id.to_string();
+        self.execute(move |connection| {
+            connection.query_row("DELETE FROM challenges WHERE id = ?1 RETURNING *", params![auth_id], to_challenge).optional()
+        }).await
+    }
+
+    async fn create_session(&self, session: &Session, max_sessions: usize) -> Result<(), StorageError> {
+        let (user_id, session_id, expires_at) = (session.user_id.clone(), session.id.clone(), session.expires_at);
+        self.execute(move |connection| {
+            let transaction = connection.transaction()?;
+            transaction.execute(
+                "INSERT INTO sessions (id, user_id, expires_at) VALUES (?1, ?2, ?3)",
+                params![session_id, user_id, expires_at as i64]
+            )?;
+            transaction.execute(
+                "DELETE FROM sessions WHERE id IN (SELECT id FROM sessions WHERE user_id = ?1 AND id <> ?2 ORDER BY expires_at DESC LIMIT -1 OFFSET ?3)",
+                params![user_id, session_id, max_sessions.saturating_sub(1) as i64]
+            )?;
+            transaction.commit()
+        }).await
+    }
+
+    async fn consume_session(&self, session_id: &str) -> Result<Option<Session>, StorageError> {
+        let session_id = session_id.to_string();
+        self.execute(move |connection| {
+            connection.query_row("DELETE FROM sessions WHERE id = ?1 RETURNING *", params![session_id], to_session).optional()
+        }).await
+    }
+
+    async fn delete_expired(&self, now: u64) -> Result<(), StorageError> {
+        self.execute(move |connection| {
+            let transaction = connection.transaction()?;
+            transaction.execute("DELETE FROM challenges WHERE expires_at <= ?1", params![now as i64])?;
+            transaction.execute("DELETE FROM sessions WHERE expires_at <= ?1", params![now as i64])?;
+            transaction.execute("DELETE FROM proofs WHERE expires_at <= ?1", params![now as i64])?;
+            transaction.execute("DELETE FROM failures WHERE expires_at <= ?1", params![now as i64])?;
+            transaction.commit()
+        }).await
+    }
+
+    async fn record_proof(&self, id: &str, expires_at: u64) -> Result<bool, StorageError> {
+        let id = id.to_string();
+        self.execute(move |connection| {
+            let inserted = connection.execute(
+                "INSERT INTO proofs (id, expires_at) VALUES (?1, ?2) ON CONFLICT (id) DO NOTHING",
+                params![id, expires_at as i64]
+            )?;
+            Ok(inserted == 1)
+        }).await
+    }
+
+    async fn delete_by_user(&self, user_id: &str) -> Result<(), StorageError> {
+        let user_id = user_id.to_string();
+        self.execute(move |connection| {
+            let transaction = connection.transaction()?;
+            transaction.execute("DELETE FROM challenges WHERE user_id = ?1", params![user_id])?;
+            transaction.execute("DELETE FROM sessions WHERE user_id = ?1", params![user_id])?;
+            transaction.commit()
+        }).await
+    }
+
+    async fn get_challenge(&self, id: &str) -> Result<Option<Challenge>, StorageError> {
+        let id = id.to_string();
+        self.execute(move |connection| {
+            connection.query_row("SELECT * FROM challenges WHERE id = ?1", params![id], to_challenge).optional()
+        }).await
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>, StorageError> {
+        let id = id.to_string();
+        self.execute(move |connection| {
+            connection.query_row("SELECT * FROM sessions WHERE id = ?1", params![id], to_session).optional()
+        }).await
+    }
+
+    async fn get_user_sessions(&self, user_id: &str) -> Result<Vec<Session>, StorageError> {
+        let user_id = user_id.to_string();
+        self.execute(move |connection| {
+            connection.prepare("SELECT * FROM sessions WHERE user_id = ?1")?.query_map(params![user_id], to_session)?.collect()
+        }).await
+    }
+
+    async fn record_failure(&self, id: &str, now: u64, expires_at: u64) -> Result<(), StorageError> {
+        let id = id.to_string();
+        self.execute(move |connection| {
+            connection.execute(
+                "INSERT INTO failures (id, count, last_failure, expires_at) VALUES (?1, 1, ?2, ?3) ON CONFLICT (id) DO UPDATE SET
+                 count = CASE WHEN failures.expires_at <= ?2 THEN 1 ELSE failures.count + 1 END, last_failure = ?2, expires_at = ?3",
+                params![id, now as i64, expires_at as i64]
+            )?;
+            Ok(())
+        }).await
+    }
+
+    async fn get_failures(&self, id: &str) -> Result<Option<Failures>, StorageError> {
+        let id = id.to_string();
+        self.execute(move |connection| {
+            connection.query_row("SELECT * FROM failures WHERE id = ?1", params![id], to_failures).optional()
+        }).await
+    }
+
+    async fn reset_failures(&self, id: &str) -> Result<(), StorageError> {
+        let id = id.to_string();
+        self.execute(move |connection| {
+            connection.execute("DELETE FROM failures WHERE id = ?1", params![id])?;
+            Ok(())
+        }).await
+    }
+
+    async fn ping_challenges(&self) -> Result<(), StorageError> {
+        self.ping_users().await
+    }
+}
\ No newline at end of file
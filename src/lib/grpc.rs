@@ -1,2 +1,4 @@
 pub mod chaum_pedersen_server;
 pub mod chaum_pedersen_client;
+pub mod error_details;
+pub mod pending_server;
\ No newline at end of file
@@ -1,16 +1,28 @@
 use num_bigint::{BigInt, Sign};
 use tokio::sync::RwLock;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use crate::chaum_pedersen::algorithm::{ChaumPedersen};
-use crate::cp_grpc::{RegisterRequest, AuthenticationChallengeRequest, AuthenticationAnswerRequest};
+use crate::chaum_pedersen::secret::{derive_secret, SecretParameters};
+use crate::cp_grpc::{self, RegisterRequest, AuthenticationChallengeRequest, AuthenticationAnswerRequest, NonInteractiveAuthenticationRequest, SecretParametersRequest, SessionRequest, ChangeSecretRequest, UnregisterRequest, RevokeSessionRequest, SessionInfo};
+use crate::grpc::chaum_pedersen_server::AUTHORIZATION_METADATA_KEY;
 use crate::cp_grpc::auth_client::AuthClient as GrpcAuthClient;
 use tonic::transport::Channel;
 use async_trait::async_trait;
 
 #[async_trait]
 pub trait AuthClient {
-    async fn register_user(&self, user: &String, x: &BigInt) -> Result<(), Box<dyn std::error::Error>>;
-    async fn authenticate_user(&self, user: &String, x: &BigInt) -> Result<String, Box<dyn std::error::Error>>;
+    async fn register_user(&self, user: &str, password: &str) -> Result<(), Box<dyn std::error::Error>>;
+    async fn authenticate_user(&self, user: &str, password: &str) -> Result<String, Box<dyn std::error::Error>>;
+    async fn authenticate_user_non_interactive(&self, user: &str, password: &str) -> Result<String, Box<dyn std::error::Error>>;
+    async fn validate_session(&self, session_id: &str) -> Result<String, Box<dyn std::error::Error>>;
+    async fn refresh_session(&self, session_id: &str) -> Result<String, Box<dyn std::error::Error>>;
+    async fn logout(&self, session_id: &str) -> Result<(), Box<dyn std::error::Error>>;
+    async fn change_password(&self, user: &str, password: &str, new_password: &str) -> Result<(), Box<dyn std::error::Error>>;
+    async fn unregister_user(&self, user: &str, password: &str) -> Result<(), Box<dyn std::error::Error>>;
+    async fn remove_user(&self, user: &str, admin_token: &str) -> Result<(), Box<dyn std::error::Error>>;
+    async fn list_sessions(&self, session_id: &str) -> Result<Vec<SessionInfo>, Box<dyn std::error::Error>>;
+    async fn revoke_session(&self, session_id: &str, session_ref: &str) -> Result<(), Box<dyn std::error::Error>>;
 }
 
 pub struct CPAuthClient<Algorithm> 
@@ -27,10 +39,49 @@ where
 {
     pub fn new(connection: Arc<RwLock<GrpcAuthClient<Channel>>>, algorithm: Arc<RwLock<Algorithm>>) -> Self {
         Self {
-            connection: connection,
-            algorithm: algorithm
+            connection,
+            algorithm
         }
     }
+
+    // Fetches the user's salt and cost parameters from the server and derives the secret x
+    async fn derive_user_secret(connection: &mut GrpcAuthClient<Channel>, algorithm: &Algorithm, user: &str, password: &str) -> Result<BigInt, Box<dyn std::error::Error>> {
+        let parameters_request = tonic::Request::new(SecretParametersRequest{
+            user: user.to_string()
+        });
+        let parameters = connection.get_secret_parameters(parameters_request).await?.into_inner();
+        let secret_parameters = SecretParameters {
+            salt: parameters.salt,
+            memory_cost: parameters.memory_cost,
+            time_cost: parameters.time_cost,
+            parallelism: parameters.parallelism
+        };
+        if !secret_parameters.is_valid() {
+            return Err("The server sent secret parameters outside of the accepted costs".into());
+        }
+
+        Ok(derive_secret(password, &secret_parameters, &algorithm.get_parameters().q)?)
+    }
+
+    // Requests a challenge for the user and returns its id together with the answer proving the knowledge of x
+    async fn answer_challenge(connection: &mut GrpcAuthClient<Channel>, algorithm: &Algorithm, user: &str, x: &BigInt) -> Result<(String, BigInt), Box<dyn std::error::Error>> {
+        let k = algorithm.generate_random();
+        let (r1, r2) = algorithm.exponentiation(&k);
+        
+        let challenge_request = tonic::Request::new(AuthenticationChallengeRequest{
+            user: user.to_string(),
+            r1: r1.to_bytes_be().1,
+            r2: r2.to_bytes_be().1
+        });
+        let challenge_response = connection.create_authentication_challenge(challenge_request).await?;
+        let challenge = challenge_response.into_inner();
+        let c = BigInt::from_bytes_be(Sign::Plus, &challenge.c);
+        if !algorithm.is_scalar(&c) {
+            return Err("The server sent a challenge outside of [1, q)".into());
+        }
+
+        Ok((challenge.auth_id, algorithm.solve_challenge(x, &k, &c)))
+    }
 }
 
 #[async_trait]
@@ -38,47 +89,185 @@ impl <Algorithm> AuthClient for CPAuthClient<Algorithm>
 where 
     Algorithm: ChaumPedersen + Send + Sync + 'static,
 {
-    async fn register_user(&self, user: &String, x: &BigInt) -> Result<(), Box<dyn std::error::Error>> {
+    async fn register_user(&self, user: &str, password: &str) -> Result<(), Box<dyn std::error::Error>> {
         let algorithm = self.algorithm.read().await;
         let mut connection = self.connection.write().await;
 
+        let secret_parameters = SecretParameters::generate();
+        let x = derive_secret(password, &secret_parameters, &algorithm.get_parameters().q)?;
         let (y1, y2) = algorithm.exponentiation(&x);
 
         let register_request = tonic::Request::new(RegisterRequest{
-            user: user.clone(),
+            user: user.to_string(),
             y1: y1.to_bytes_be().1,
-            y2: y2.to_bytes_be().1
+            y2: y2.to_bytes_be().1,
+            secret_parameters: Some(cp_grpc::SecretParameters {
+                salt: secret_parameters.salt,
+                memory_cost: secret_parameters.memory_cost,
+                time_cost: secret_parameters.time_cost,
+                parallelism: secret_parameters.parallelism
+            })
         });
         connection.register(register_request).await?;
 
         Ok(())
     }
 
-    async fn authenticate_user(&self, user: &String, x: &BigInt) -> Result<String, Box<dyn std::error::Error>> {
-        let mut algorithm = self.algorithm.write().await;
+    async fn authenticate_user(&self, user: &str, password: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let algorithm = self.algorithm.read().await;
         let mut connection = self.connection.write().await;
 
+        let x = Self::derive_user_secret(&mut connection, &algorithm, user, password).await?;
+        let (auth_id, s) = Self::answer_challenge(&mut connection, &algorithm, user, &x).await?;
+
+        let answer_request = tonic::Request::new(AuthenticationAnswerRequest{
+            auth_id,
+            s: s.to_bytes_be().1
+        });
+        let answer_response = connection.verify_authentication(answer_request).await?;
+        let answer = answer_response.into_inner();
+
+        Ok(answer.session_id)
+    }
+
+    async fn authenticate_user_non_interactive(&self, user: &str, password: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let algorithm = self.algorithm.read().await;
+        let mut connection = self.connection.write().await;
+
+        let x = Self::derive_user_secret(&mut connection, &algorithm, user, password).await?;
+        let (y1, y2) = algorithm.exponentiation(&x);
         let k = algorithm.generate_random();
         let (r1, r2) = algorithm.exponentiation(&k);
-        
-        let challenge_request = tonic::Request::new(AuthenticationChallengeRequest{
-            user: user.clone(),
-            r1: r1.to_bytes_be().1,
-            r2: r2.to_bytes_be().1
-        });
-        let challenge_response = connection.create_authentication_challenge(challenge_request).await?;
-        let challenge = challenge_response.into_inner();
-        let c = BigInt::from_bytes_be(Sign::Plus, &challenge.c);
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
 
+        let c = algorithm.challenge_from_transcript(user, &y1, &y2, &r1, &r2, &timestamp.to_be_bytes());
         let s = algorithm.solve_challenge(&x, &k, &c);
 
-        let answer_request = tonic::Request::new(AuthenticationAnswerRequest{
-            auth_id: challenge.auth_id,
-            s: s.to_bytes_be().1
+        let proof_request = tonic::Request::new(NonInteractiveAuthenticationRequest{
+            user: user.to_string(),
+            r1: r1.to_bytes_be().1,
+            r2: r2.to_bytes_be().1,
+            s: s.to_bytes_be().1,
+            timestamp
         });
-        let answer_response = connection.verify_authentication(answer_request).await?;
+        let answer_response = connection.verify_non_interactive_authentication(proof_request).await?;
         let answer = answer_response.into_inner();
 
         Ok(answer.session_id)
     }
+
+    async fn validate_session(&self, session_id: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let mut connection = self.connection.write().await;
+
+        let session_request = tonic::Request::new(SessionRequest{
+            session_id: session_id.to_string()
+        });
+        let session = connection.validate_session(session_request).await?.into_inner();
+
+        Ok(session.user)
+    }
+
+    async fn refresh_session(&self, session_id: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let mut connection = self.connection.write().await;
+
+        let session_request = tonic::Request::new(SessionRequest{
+            session_id: session_id.to_string()
+        });
+        let session = connection.refresh_session(session_request).await?.into_inner();
+
+        Ok(session.session_id)
+    }
+
+    async fn logout(&self, session_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut connection = self.connection.write().await;
+
+        let session_request = tonic::Request::new(SessionRequest{
+            session_id: session_id.to_string()
+        });
+        connection.logout(session_request).await?;
+
+        Ok(())
+    }
+
+    // Proves the knowledge of the current password in the same exchange which sets the new one
+    async fn change_password(&self, user: &str, password: &str, new_password: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let algorithm = self.algorithm.read().await;
+        let mut connection = self.connection.write().await;
+
+        let x = Self::derive_user_secret(&mut connection, &algorithm, user, password).await?;
+        let (auth_id, s) = Self::answer_challenge(&mut connection, &algorithm, user, &x).await?;
+
+        let secret_parameters = SecretParameters::generate();
+        let new_x = derive_secret(new_password, &secret_parameters, &algorithm.get_parameters().q)?;
+        let (y1, y2) = algorithm.exponentiation(&new_x);
+
+        let change_request = tonic::Request::new(ChangeSecretRequest{
+            auth_id,
+            s: s.to_bytes_be().1,
+            y1: y1.to_bytes_be().1,
+            y2: y2.to_bytes_be().1,
+            secret_parameters: Some(cp_grpc::SecretParameters {
+                salt: secret_parameters.salt,
+                memory_cost: secret_parameters.memory_cost,
+                time_cost: secret_parameters.time_cost,
+                parallelism: secret_parameters.parallelism
+            })
+        });
+        connection.change_secret(change_request).await?;
+
+        Ok(())
+    }
+
+    async fn unregister_user(&self, user: &str, password: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let algorithm = self.algorithm.read().await;
+        let mut connection = self.connection.write().await;
+
+        let x = Self::derive_user_secret(&mut connection, &algorithm, user, password).await?;
+        let (auth_id, s) = Self::answer_challenge(&mut connection, &algorithm, user, &x).await?;
+
+        let unregister_request = tonic::Request::new(UnregisterRequest{
+            auth_id,
+            s: s.to_bytes_be().1,
+            user: user.to_string()
+        });
+        connection.unregister(unregister_request).await?;
+
+        Ok(())
+    }
+
+    async fn remove_user(&self, user: &str, admin_token: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut connection = self.connection.write().await;
+
+        let mut unregister_request = tonic::Request::new(UnregisterRequest{
+            user: user.to_string(),
+            ..Default::default()
+        });
+        unregister_request.metadata_mut().insert(AUTHORIZATION_METADATA_KEY, format!("Bearer {}", admin_token).parse()?);
+        connection.unregister(unregister_request).await?;
+
+        Ok(())
+    }
+
+    async fn list_sessions(&self, session_id: &str) -> Result<Vec<SessionInfo>, Box<dyn std::error::Error>> {
+        let mut connection = self.connection.write().await;
+
+        let session_request = tonic::Request::new(SessionRequest{
+            session_id: session_id.to_string()
+        });
+        let sessions = connection.list_sessions(session_request).await?.into_inner();
+
+        Ok(sessions.sessions)
+    }
+
+    async fn revoke_session(&self, session_id: &str, session_ref: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut connection = self.connection.write().await;
+
+        let revoke_request = tonic::Request::new(RevokeSessionRequest{
+            session_id: session_id.to_string(),
+            session_ref: session_ref.to_string()
+        });
+        connection.revoke_session(revoke_request).await?;
+
+        Ok(())
+    }
 }
\ No newline at end of file
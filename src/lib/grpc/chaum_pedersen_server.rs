@@ -1,27 +1,34 @@
-use std::sync::{RwLock, Arc};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::logic::chaum_pedersen_model::{ValidationErrors, UserChallengeRequest, UserSolution};
-use crate::{cp_grpc::*, logic::chaum_pedersen_model::UserRegistration};
+
+use crate::logic::chaum_pedersen_model::{AuthError, UserChallengeRequest, UserSolution, UserSecretChange, UserProof};
+use crate::{chaum_pedersen, cp_grpc::*, logic::chaum_pedersen_model::UserRegistration};
 use crate::cp_grpc::auth_server::Auth;
 use crate::logic::chaum_pedersen_logic::ChaumPedersenLogic;
+use super::error_details::{with_error_info, with_retry_info, ErrorReason};
 use num_bigint::{BigInt, Sign};
-use tonic::{Request, Response, Status};
+use tonic::{Code, Request, Response, Status};
+
+// The metadata which carries the admin token
+pub const AUTHORIZATION_METADATA_KEY: &str = "authorization";
 
 #[derive(Debug)]
 pub struct CPAuthServer<Logic> 
 where 
     Logic: ChaumPedersenLogic + Send + Sync + 'static,
 {
-    logic: Arc<RwLock<Logic>>,
+    logic: Arc<Logic>,
 }
 
 impl<Logic> CPAuthServer<Logic> 
 where 
     Logic: ChaumPedersenLogic + Send + Sync + 'static,
 {
-    pub fn new(logic: Arc<RwLock<Logic>>) -> Self {
+    pub fn new(logic: Arc<Logic>) -> Self {
         Self {
-            logic: logic
+            logic
         }
     }
 }
@@ -32,48 +39,66 @@ where
     Logic: ChaumPedersenLogic + Send + Sync + 'static,
 {
     async fn get_authentication_parameters(&self, _request: Request<()>) -> Result<Response<AuthenticationParametersResponse>, Status> {
-        let logic = self.logic.read().unwrap();
-
-        let parameters = logic.get_parameters().map_err(to_tonic_error)?;
+        let parameters = self.logic.get_parameters().await.map_err(to_tonic_error)?;
 
         let response = AuthenticationParametersResponse {
             p: parameters.p.to_bytes_be().1,
             q: parameters.q.to_bytes_be().1,
             g: parameters.g.to_bytes_be().1,
             h: parameters.h.to_bytes_be().1,
-            bit_size: parameters.bit_size.into()
+            bit_size: parameters.bit_size.into(),
+            group: parameters.group.to_string(),
+            seed: parameters.seed
         };
         Ok(Response::new(response))
     }
 
     async fn register(&self, _request: Request<RegisterRequest>) -> Result<Response<RegisterResponse>, Status> {
-        let logic = self.logic.read().unwrap();
-
         let data = _request.get_ref();
         let y1 = BigInt::from_bytes_be(Sign::Plus, &data.y1);
         let y2 = BigInt::from_bytes_be(Sign::Plus, &data.y2);
 
-        logic.register_user(&UserRegistration{
+        let secret_parameters = data.secret_parameters.clone().unwrap_or_default();
+
+        self.logic.register_user(&UserRegistration{
             user: data.user.clone(),
-            y1: y1,
-            y2: y2
-        }).map_err(to_tonic_error)?;
+            y1,
+            y2,
+            secret_parameters: chaum_pedersen::secret::SecretParameters {
+                salt: secret_parameters.salt,
+                memory_cost: secret_parameters.memory_cost,
+                time_cost: secret_parameters.time_cost,
+                parallelism: secret_parameters.parallelism
+            }
+        }).await.map_err(to_tonic_error)?;
 
         Ok(Response::new(RegisterResponse::default()))
     }
 
-    async fn create_authentication_challenge(&self, _request: Request<AuthenticationChallengeRequest>) -> Result<Response<AuthenticationChallengeResponse>, Status> {
-        let logic = self.logic.read().unwrap();
+    async fn get_secret_parameters(&self, _request: Request<SecretParametersRequest>) -> Result<Response<SecretParameters>, Status> {
+        let data = _request.get_ref();
+        let secret_parameters = self.logic.get_secret_parameters(&data.user).await.map_err(to_tonic_error)?;
+
+        let response = SecretParameters {
+            salt: secret_parameters.salt,
+            memory_cost: secret_parameters.memory_cost,
+            time_cost: secret_parameters.time_cost,
+            parallelism: secret_parameters.parallelism
+        };
+        Ok(Response::new(response))
+    }
 
+    async fn create_authentication_challenge(&self, _request: Request<AuthenticationChallengeRequest>) -> Result<Response<AuthenticationChallengeResponse>, Status> {
         let data = _request.get_ref();
         let r1 = BigInt::from_bytes_be(Sign::Plus, &data.r1);
         let r2 = BigInt::from_bytes_be(Sign::Plus, &data.r2);
         
-        let challenge = logic.authentication_challenge(&UserChallengeRequest{
+        let challenge = self.logic.authentication_challenge(&UserChallengeRequest{
             user: data.user.clone(),
-            r1: r1,
-            r2: r2
-        }).map_err(to_tonic_error)?;
+            r1,
+            r2,
+            peer: peer(&_request)
+        }).await.map_err(to_tonic_error)?;
 
         let challenge_response = AuthenticationChallengeResponse {
             c: challenge.c.to_bytes_be().1,
@@ -83,31 +108,178 @@ where
     }
 
     async fn verify_authentication(&self, _request: Request<AuthenticationAnswerRequest>) -> Result<Response<AuthenticationAnswerResponse>, Status> {
-        let logic = self.logic.read().unwrap();
-
         let data = _request.get_ref();
         let s = BigInt::from_bytes_be(Sign::Plus, &data.s);
 
         let user_solution = UserSolution{
             auth_id: data.auth_id.clone(),
-            s: s
+            s,
+            peer: peer(&_request)
         };
         
-        let session = logic.solve_challenge(&user_solution).map_err(to_tonic_error)?;
+        let session = self.logic.solve_challenge(&user_solution).await.map_err(to_tonic_error)?;
+
+        let response = AuthenticationAnswerResponse {
+            session_id: session.session_id.clone(),
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn verify_non_interactive_authentication(&self, _request: Request<NonInteractiveAuthenticationRequest>) -> Result<Response<AuthenticationAnswerResponse>, Status> {
+        let data = _request.get_ref();
+        let user_proof = UserProof{
+            user: data.user.clone(),
+            r1: BigInt::from_bytes_be(Sign::Plus, &data.r1),
+            r2: BigInt::from_bytes_be(Sign::Plus, &data.r2),
+            s: BigInt::from_bytes_be(Sign::Plus, &data.s),
+            timestamp: data.timestamp,
+            peer: peer(&_request)
+        };
+
+        let session = self.logic.verify_non_interactive_proof(&user_proof).await.map_err(to_tonic_error)?;
 
         let response = AuthenticationAnswerResponse {
             session_id: session.session_id.clone(),
         };
         Ok(Response::new(response))
     }
+
+    async fn validate_session(&self, _request: Request<SessionRequest>) -> Result<Response<SessionValidationResponse>, Status> {
+        let data = _request.get_ref();
+        let session = self.logic.validate_session(&data.session_id).await.map_err(to_tonic_error)?;
+
+        let response = SessionValidationResponse {
+            user: session.user
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn refresh_session(&self, _request: Request<SessionRequest>) -> Result<Response<AuthenticationAnswerResponse>, Status> {
+        let data = _request.get_ref();
+        let session = self.logic.refresh_session(&data.session_id).await.map_err(to_tonic_error)?;
+
+        let response = AuthenticationAnswerResponse {
+            session_id: session.session_id.clone(),
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn logout(&self, _request: Request<SessionRequest>) -> Result<Response<LogoutResponse>, Status> {
+        let data = _request.get_ref();
+        self.logic.logout(&data.session_id).await.map_err(to_tonic_error)?;
+
+        Ok(Response::new(LogoutResponse::default()))
+    }
+
+    async fn get_session_key(&self, _request: Request<()>) -> Result<Response<SessionKeyResponse>, Status> {
+        let key = self.logic.get_session_key().await.map_err(to_tonic_error)?;
+
+        let response = SessionKeyResponse {
+            algorithm: key.algorithm,
+            key_id: key.key_id,
+            public_key: key.public_key,
+            public_key_pem: key.public_key_pem
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn change_secret(&self, _request: Request<ChangeSecretRequest>) -> Result<Response<ChangeSecretResponse>, Status> {
+        let data = _request.get_ref();
+        let secret_parameters = data.secret_parameters.clone().unwrap_or_default();
+
+        self.logic.change_secret(&UserSecretChange{
+            solution: UserSolution{
+                auth_id: data.auth_id.clone(),
+                s: BigInt::from_bytes_be(Sign::Plus, &data.s),
+                peer: peer(&_request)
+            },
+            y1: BigInt::from_bytes_be(Sign::Plus, &data.y1),
+            y2: BigInt::from_bytes_be(Sign::Plus, &data.y2),
+            secret_parameters: chaum_pedersen::secret::SecretParameters {
+                salt: secret_parameters.salt,
+                memory_cost: secret_parameters.memory_cost,
+                time_cost: secret_parameters.time_cost,
+                parallelism: secret_parameters.parallelism
+            }
+        }).await.map_err(to_tonic_error)?;
+
+        Ok(Response::new(ChangeSecretResponse::default()))
+    }
+
+    async fn unregister(&self, _request: Request<UnregisterRequest>) -> Result<Response<UnregisterResponse>, Status> {
+        let admin_token = bearer_token(&_request);
+        let data = _request.get_ref();
+
+        match admin_token {
+            Some(admin_token) => self.logic.remove_user(&data.user, admin_token, peer(&_request)).await,
+            None => self.logic.unregister_user(&data.user, &UserSolution{
+                auth_id: data.auth_id.clone(),
+                s: BigInt::from_bytes_be(Sign::Plus, &data.s),
+                peer: peer(&_request)
+            }).await
+        }.map_err(to_tonic_error)?;
+
+        Ok(Response::new(UnregisterResponse::default()))
+    }
+
+    async fn list_sessions(&self, _request: Request<SessionRequest>) -> Result<Response<SessionListResponse>, Status> {
+        let data = _request.get_ref();
+        let sessions = self.logic.list_sessions(&data.session_id).await.map_err(to_tonic_error)?;
+
+        let response = SessionListResponse {
+            sessions: sessions.into_iter().map(|session| SessionInfo {
+                session_ref: session.session_ref,
+                expires_at: session.expires_at,
+                current: session.current
+            }).collect()
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn revoke_session(&self, _request: Request<RevokeSessionRequest>) -> Result<Response<RevokeSessionResponse>, Status> {
+        let data = _request.get_ref();
+        self.logic.revoke_session(&data.session_id, &data.session_ref).await.map_err(to_tonic_error)?;
+
+        Ok(Response::new(RevokeSessionResponse::default()))
+    }
+}
+
+// The address of the client, which is only known if the request came in over a connection
+fn peer<T>(request: &Request<T>) -> Option<IpAddr> {
+    request.remote_addr().map(|address| address.ip())
+}
+
+// A request with any authorization metadata is treated as admin request, so that a malformed token is rejected instead of ignored
+fn bearer_token<T>(request: &Request<T>) -> Option<&str> {
+    let authorization = request.metadata().get(AUTHORIZATION_METADATA_KEY)?;
+    Some(authorization.to_str().ok().and_then(|value| value.strip_prefix("Bearer ")).unwrap_or_default())
 }
 
-//Use internal messages to make errors more secific
-fn to_tonic_error(error: ValidationErrors) -> Status {
+// Unknown users and wrong proofs share one reason and message, so that they can not be told apart
+fn to_tonic_error(error: AuthError) -> Status {
+    let message = error.to_string();
     match error {
-        ValidationErrors::InvalidArgument => Status::invalid_argument("Invalid argument"),
-        ValidationErrors::NotFound => Status::not_found("Not found"),
-        ValidationErrors::AlreadyExists => Status::already_exists("Already exists"),
-        ValidationErrors::Unauthenticated => Status::unauthenticated("Unauthenticated"),
+        AuthError::EmptyField(field) => with_error_info(Code::InvalidArgument, message, ErrorReason::EmptyField, Some(field)),
+        AuthError::NotAnElement(field) => with_error_info(Code::InvalidArgument, message, ErrorReason::NotAnElement, Some(field)),
+        AuthError::ScalarOutOfRange(field) => with_error_info(Code::InvalidArgument, message, ErrorReason::ScalarOutOfRange, Some(field)),
+        AuthError::InvalidSecretParameters => with_error_info(Code::InvalidArgument, message, ErrorReason::InvalidSecretParameters, Some("secret_parameters")),
+        AuthError::UserAlreadyExists => with_error_info(Code::AlreadyExists, message, ErrorReason::UserAlreadyExists, Some("user")),
+        AuthError::AuthenticationFailed => with_error_info(Code::Unauthenticated, message, ErrorReason::AuthenticationFailed, None),
+        AuthError::ChallengeNotFound => with_error_info(Code::Unauthenticated, message, ErrorReason::ChallengeNotFound, Some("auth_id")),
+        AuthError::ChallengeExpired => with_error_info(Code::Unauthenticated, message, ErrorReason::ChallengeExpired, Some("auth_id")),
+        AuthError::ProofExpired => with_error_info(Code::Unauthenticated, message, ErrorReason::ProofExpired, Some("timestamp")),
+        AuthError::ProofAlreadyUsed => with_error_info(Code::Unauthenticated, message, ErrorReason::ProofAlreadyUsed, None),
+        AuthError::SessionNotFound => with_error_info(Code::Unauthenticated, message, ErrorReason::SessionNotFound, Some("session_id")),
+        AuthError::SessionExpired => with_error_info(Code::Unauthenticated, message, ErrorReason::SessionExpired, Some("session_id")),
+        AuthError::SessionRefNotFound => with_error_info(Code::NotFound, message, ErrorReason::SessionRefNotFound, Some("session_ref")),
+        AuthError::InvalidSessionToken => with_error_info(Code::Unauthenticated, message, ErrorReason::InvalidSessionToken, Some("session_id")),
+        AuthError::SessionTokensDisabled => with_error_info(Code::FailedPrecondition, message, ErrorReason::SessionTokensDisabled, None),
+        AuthError::AdminAccessDenied => with_error_info(Code::PermissionDenied, message, ErrorReason::AdminAccessDenied, None),
+        AuthError::UserNotFound => with_error_info(Code::NotFound, message, ErrorReason::UserNotFound, Some("user")),
+        AuthError::TooManyAttempts(retry_after) => with_retry_info(Code::ResourceExhausted, message, ErrorReason::TooManyAttempts, Duration::from_secs(retry_after)),
+        AuthError::Storage(error) => {
+            eprintln!("{}", error);
+            with_error_info(Code::Unavailable, message, ErrorReason::StorageUnavailable, None)
+        },
     }
 }
\ No newline at end of file
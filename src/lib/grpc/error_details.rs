@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::Duration;
+
+use prost::Message;
+use tonic::{Code, Status};
+
+use crate::google_rpc::{ErrorInfo, RetryInfo, Status as RpcStatus};
+
+// The domain of all ErrorInfo details sent by the server
+pub const ERROR_DOMAIN: &str = "zkp_auth";
+// The key of the ErrorInfo metadata which names the invalid field of the request
+pub const FIELD_METADATA_KEY: &str = "field";
+const ERROR_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+const RETRY_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+// The reasons of the ErrorInfo details, so that clients do not have to parse the messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorReason {
+    EmptyField,
+    NotAnElement,
+    ScalarOutOfRange,
+    InvalidSecretParameters,
+    UserAlreadyExists,
+    AuthenticationFailed,
+    ChallengeNotFound,
+    ChallengeExpired,
+    ProofExpired,
+    SessionNotFound,
+    SessionExpired,
+    StorageUnavailable,
+    ProofAlreadyUsed,
+    ServerStarting,
+    InvalidSessionToken,
+    SessionTokensDisabled,
+    AdminAccessDenied,
+    UserNotFound,
+    TooManyAttempts,
+    SessionRefNotFound,
+}
+
+impl ErrorReason {
+    pub const ALL: [ErrorReason; 20] = [
+        ErrorReason::EmptyField,
+        ErrorReason::NotAnElement,
+        ErrorReason::ScalarOutOfRange,
+        ErrorReason::InvalidSecretParameters,
+        ErrorReason::UserAlreadyExists,
+        ErrorReason::AuthenticationFailed,
+        ErrorReason::ChallengeNotFound,
+        ErrorReason::ChallengeExpired,
+        ErrorReason::ProofExpired,
+        ErrorReason::SessionNotFound,
+        ErrorReason::SessionExpired,
+        ErrorReason::StorageUnavailable,
+        ErrorReason::ProofAlreadyUsed,
+        ErrorReason::ServerStarting,
+        ErrorReason::InvalidSessionToken,
+        ErrorReason::SessionTokensDisabled,
+        ErrorReason::AdminAccessDenied,
+        ErrorReason::UserNotFound,
+        ErrorReason::TooManyAttempts,
+        ErrorReason::SessionRefNotFound,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorReason::EmptyField => "EMPTY_FIELD",
+            ErrorReason::NotAnElement => "NOT_AN_ELEMENT",
+            ErrorReason::ScalarOutOfRange => "SCALAR_OUT_OF_RANGE",
+            ErrorReason::InvalidSecretParameters => "INVALID_SECRET_PARAMETERS",
+            ErrorReason::UserAlreadyExists => "USER_ALREADY_EXISTS",
+            ErrorReason::AuthenticationFailed => "AUTHENTICATION_FAILED",
+            ErrorReason::ChallengeNotFound => "CHALLENGE_NOT_FOUND",
+            ErrorReason::ChallengeExpired => "CHALLENGE_EXPIRED",
+            ErrorReason::ProofExpired => "PROOF_EXPIRED",
+            ErrorReason::SessionNotFound => "SESSION_NOT_FOUND",
+            ErrorReason::SessionExpired => "SESSION_EXPIRED",
+            ErrorReason::StorageUnavailable => "STORAGE_UNAVAILABLE",
+            ErrorReason::ProofAlreadyUsed => "PROOF_ALREADY_USED",
+            ErrorReason::ServerStarting => "SERVER_STARTING",
+            ErrorReason::InvalidSessionToken => "INVALID_SESSION_TOKEN",
+            ErrorReason::SessionTokensDisabled => "SESSION_TOKENS_DISABLED",
+            ErrorReason::AdminAccessDenied => "ADMIN_ACCESS_DENIED",
+            ErrorReason::UserNotFound => "USER_NOT_FOUND",
+            ErrorReason::TooManyAttempts => "TOO_MANY_ATTEMPTS",
+            ErrorReason::SessionRefNotFound => "SESSION_REF_NOT_FOUND",
+        }
+    }
+}
+
+impl Display for ErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ErrorReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ErrorReason::ALL.into_iter()
+            .find(|reason| reason.as_str() == s)
+            .ok_or(format!("Unknown error reason {}", s))
+    }
+}
+
+// Builds a status which carries an ErrorInfo in its details, the field is added to the metadata if given
+pub fn with_error_info(code: Code, message: String, reason: ErrorReason, field: Option<&str>) -> Status {
+    with_details(code, message, vec![error_info_detail(reason, field)])
+}
+
+// Builds a status which carries an ErrorInfo and a RetryInfo with the delay after which the request may succeed
+pub fn with_retry_info(code: Code, message: String, reason: ErrorReason, retry_delay: Duration) -> Status {
+    let info = RetryInfo {
+        retry_delay: Some(prost_types::Duration {
+            seconds: retry_delay.as_secs() as i64,
+            nanos: retry_delay.subsec_nanos() as i32
+        })
+    };
+    with_details(code, message, vec![error_info_detail(reason, None), prost_types::Any {
+        type_url: RETRY_INFO_TYPE_URL.to_string(),
+        value: info.encode_to_vec()
+    }])
+}
+
+fn error_info_detail(reason: ErrorReason, field: Option<&str>) -> prost_types::Any {
+    let mut metadata = HashMap::new();
+    if let Some(field) = field {
+        metadata.insert(FIELD_METADATA_KEY.to_string(), field.to_string());
+    }
+    let info = ErrorInfo {
+        reason: reason.to_string(),
+        domain: ERROR_DOMAIN.to_string(),
+        metadata
+    };
+    prost_types::Any {
+        type_url: ERROR_INFO_TYPE_URL.to_string(),
+        value: info.encode_to_vec()
+    }
+}
+
+fn with_details(code: Code, message: String, details: Vec<prost_types::Any>) -> Status {
+    let details = RpcStatus {
+        code: code as i32,
+        message: message.clone(),
+        details
+    };
+    Status::with_details(code, message, details.encode_to_vec().into())
+}
+
+// Reads the ErrorInfo from the details of a status sent by the server
+pub fn error_info(status: &Status) -> Option<ErrorInfo> {
+    let details = RpcStatus::decode(status.details()).ok()?;
+    details.details.iter()
+        .filter(|detail| detail.type_url == ERROR_INFO_TYPE_URL)
+        .find_map(|detail| ErrorInfo::decode(detail.value.as_slice()).ok())
+}
+
+// Reads the reason from the details of a status, None if the server did not send one
+pub fn error_reason(status: &Status) -> Option<ErrorReason> {
+    error_info(status)?.reason.parse().ok()
+}
+
+// Reads the delay after which the client may retry, None if the server did not send one
+pub fn retry_delay(status: &Status) -> Option<Duration> {
+    let details = RpcStatus::decode(status.details()).ok()?;
+    let info = details.details.iter()
+        .filter(|detail| detail.type_url == RETRY_INFO_TYPE_URL)
+        .find_map(|detail| RetryInfo::decode(detail.value.as_slice()).ok())?;
+    let delay = info.retry_delay?;
+    Some(Duration::new(delay.seconds.try_into().ok()?, delay.nanos.try_into().ok()?))
+}
\ No newline at end of file
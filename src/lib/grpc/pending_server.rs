@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use once_cell::sync::OnceCell;
+use tonic::{Code, Request, Response, Status};
+
+use crate::cp_grpc::*;
+use crate::cp_grpc::auth_server::Auth;
+use super::error_details::{with_error_info, ErrorReason};
+
+// Answers every request with UNAVAILABLE until the server is set,
+// so that the port already answers health checks while the parameters are generated
+pub struct PendingAuthServer<Server>
+where
+    Server: Auth
+{
+    server: Arc<OnceCell<Server>>
+}
+
+impl<Server> PendingAuthServer<Server>
+where
+    Server: Auth
+{
+    pub fn new(server: Arc<OnceCell<Server>>) -> Self {
+        Self {
+            server
+        }
+    }
+}
+
+fn starting() -> Status {
+    with_error_info(Code::Unavailable, String::from("The server is starting"), ErrorReason::ServerStarting, None)
+}
+
+#[tonic::async_trait]
+impl<Server> Auth for PendingAuthServer<Server>
+where
+    Server: Auth
+{
+    async fn get_authentication_parameters(&self, request: Request<()>) -> Result<Response<AuthenticationParametersResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.get_authentication_parameters(request).await
+    }
+
+    async fn register(&self, request: Request<RegisterRequest>) -> Result<Response<RegisterResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.register(request).await
+    }
+
+    async fn get_secret_parameters(&self, request: Request<SecretParametersRequest>) -> Result<Response<SecretParameters>, Status> {
+        self.server.get().ok_or_else(starting)?.get_secret_parameters(request).await
+    }
+
+    async fn create_authentication_challenge(&self, request: Request<AuthenticationChallengeRequest>) -> Result<Response<AuthenticationChallengeResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.create_authentication_challenge(request).await
+    }
+
+    async fn verify_authentication(&self, request: Request<AuthenticationAnswerRequest>) -> Result<Response<AuthenticationAnswerResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.verify_authentication(request).await
+    }
+
+    async fn verify_non_interactive_authentication(&self, request: Request<NonInteractiveAuthenticationRequest>) -> Result<Response<AuthenticationAnswerResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.verify_non_interactive_authentication(request).await
+    }
+
+    async fn validate_session(&self, request: Request<SessionRequest>) -> Result<Response<SessionValidationResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.validate_session(request).await
+    }
+
+    async fn refresh_session(&self, request: Request<SessionRequest>) -> Result<Response<AuthenticationAnswerResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.refresh_session(request).await
+    }
+
+    async fn logout(&self, request: Request<SessionRequest>) -> Result<Response<LogoutResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.logout(request).await
+    }
+
+    async fn get_session_key(&self, request: Request<()>) -> Result<Response<SessionKeyResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.get_session_key(request).await
+    }
+
+    async fn change_secret(&self, request: Request<ChangeSecretRequest>) -> Result<Response<ChangeSecretResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.change_secret(request).await
+    }
+
+    async fn unregister(&self, request: Request<UnregisterRequest>) -> Result<Response<UnregisterResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.unregister(request).await
+    }
+
+    async fn list_sessions(&self, request: Request<SessionRequest>) -> Result<Response<SessionListResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.list_sessions(request).await
+    }
+
+    async fn revoke_session(&self, request: Request<RevokeSessionRequest>) -> Result<Response<RevokeSessionResponse>, Status> {
+        self.server.get().ok_or_else(starting)?.revoke_session(request).await
+    }
+}
\ No newline at end of file
@@ -10,28 +10,81 @@ pub mod grpc;
 mod data_access;
 mod logic;
 
-use std::{sync::Arc, sync::RwLock, error::Error};
+use std::{sync::Arc, error::Error};
 use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::Duration;
 use cp_grpc::auth_server::Auth;
 use grpc::chaum_pedersen_client::AuthClient;
-use logic::chaum_pedersen_logic::ChaumPedersenLogicImpl;
+use logic::chaum_pedersen_logic::{ChaumPedersenLogicImpl, LogicSettings};
 use logic::chaum_pedesen_validation::ChaumPedersenValidationImpl;
 use chaum_pedersen::algorithm::{ChaumPedersenAlgorthim, ChaumPedersenParameters};
+use chaum_pedersen::encoding::EncodingError;
+use chaum_pedersen::group::GroupType;
+use chaum_pedersen::named_group::NamedGroup;
+use data_access::access::DataAccess;
 use data_access::map_access::MapDataAccess;
+use data_access::sqlite_access::SqliteDataAccess;
+use data_access::postgres_access::PostgresDataAccess;
+use data_access::redis_access::RedisChallengeAccess;
+use data_access::split_access::SplitDataAccess;
+use logic::current_timestamp;
+use logic::session_token::SessionTokens;
+use logic::rate_limit::RateLimit;
 use grpc::{chaum_pedersen_server::CPAuthServer, chaum_pedersen_client::CPAuthClient};
-use std::{hash::{Hash, Hasher}};
-use num_bigint::{BigInt, ToBigInt, Sign};
-use rustc_hash::FxHasher;
+use num_bigint::{BigInt, Sign};
 use tonic::codegen::StdError;
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity, ServerTlsConfig};
+use tonic_health::ServingStatus;
+use tonic_health::server::HealthReporter;
+use tonic_reflection::server::{ServerReflection, ServerReflectionServer};
 
-const DEFAULT_BIT_SIZE: &str = "256";
-const DEFAULT_FIXED_PARAMETERS: &str = "false";
+const DEFAULT_BIT_SIZE: &str = "2048";
+const DEFAULT_FIXED_PARAMETERS: &str = "true";
+const DEFAULT_GROUP: &str = "modp";
+const DEFAULT_NAMED_GROUP: &str = "";
+const DEFAULT_PARAMETERS_FILE: &str = "";
+const DEFAULT_CHALLENGE_TTL: &str = "60";
+const DEFAULT_SESSION_TTL: &str = "3600";
+const DEFAULT_SESSION_TOKENS: &str = "false";
+const DEFAULT_SESSION_KEY_FILE: &str = "";
+const DEFAULT_ADMIN_TOKEN: &str = "";
+const DEFAULT_USER_FAILURE_LIMIT: &str = "5";
+const DEFAULT_PEER_FAILURE_LIMIT: &str = "20";
+const DEFAULT_BASE_LOCKOUT: &str = "1";
+const DEFAULT_MAX_LOCKOUT: &str = "900";
+const DEFAULT_MAX_CHALLENGES_PER_USER: &str = "5";
+const DEFAULT_MAX_SESSIONS_PER_USER: &str = "10";
+const MINIMUM_ADMIN_TOKEN_LENGTH: usize = 32;
+const DEFAULT_REVEAL_EXISTING_USERS: &str = "true";
+const DEFAULT_DECOY_SECRET: &str = "";
+const DEFAULT_CLEANUP_INTERVAL: &str = "60";
+const DEFAULT_HEALTH_CHECK_INTERVAL: &str = "10";
+const DEFAULT_DATABASE_URL: &str = "";
+const DEFAULT_DATABASE_POOL_SIZE: &str = "8";
+const DEFAULT_REDIS_URL: &str = "";
+const DEFAULT_PROOF_VALIDITY: &str = "30";
+const DEFAULT_MINIMUM_BIT_SIZE: &str = "2048";
 const DEFAULT_PORT: &str = "50051";
 const DEFAULT_HOST: &str = "[::1]";
+const DEFAULT_TLS_PATH: &str = "";
+const DEFAULT_TLS_DOMAIN: &str = "";
 
 #[doc(hidden)]
 pub mod cp_grpc {
     tonic::include_proto!("zkp_auth"); // The string specified here must match the proto package name
+
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("zkp_auth_descriptor");
+}
+
+// The name under which the AuthServer is registered and its health is reported
+const AUTH_SERVICE_NAME: &str = "zkp_auth.Auth";
+
+#[doc(hidden)]
+pub mod google_rpc {
+    tonic::include_proto!("google.rpc");
 }
 
 #[doc = include_str!("../../README.md")]
@@ -44,19 +97,23 @@ pub struct ReadmeDoctests;
 /// This is how the configuration can be modified inside the code:
 /// ```
 /// # use auth_lib::Config;
-/// let mut config = Config::build();
+/// let mut config = Config::build()?;
 /// config.bit_size = 128;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 pub struct Config {
     /// The bit size of the prime number used in the algorithm
     /// 
-    /// Default: 256
+    /// Default: 2048
     pub bit_size: u16,
-    /// Whether to use fixed parameters or generate new ones
-    /// Caution! If this is set to true and the bit size is bigger than 256,
-    /// it will take a long time for the server to start.
+    /// Whether to use built-in parameters instead of generating a new safe prime at startup.
+    /// If set to true, the bit sizes 2048, 3072, 4096, 6144 and 8192 are an alias for the RFC 7919 group
+    /// of that size (`ffdhe2048` to `ffdhe8192`), not the RFC 3526 MODP group, and the server starts instantly.
+    /// The server logs which named group it uses, `named_group` takes precedence if both are set.
+    /// The built-in 256-bit group is only meant for tests.
+    /// Caution! If this is set to false, generating the safe prime can take minutes for 2048 bits and more.
     /// 
-    /// Default: false
+    /// Default: true
     pub fixed_parameters: bool,
     /// The port on which the server will listen
     /// 
@@ -65,33 +122,349 @@ pub struct Config {
     /// The host on which the server will listen
     /// 
     /// Default: [::1]
-    pub host: String
+    pub host: String,
+    /// The group in which the algorithm operates.
+    /// Either `modp` for the multiplicative group modulo a safe prime of `bit_size` bits
+    /// or `ristretto255` for the prime-order elliptic curve group. For `ristretto255`
+    /// the `bit_size` and `fixed_parameters` settings are ignored.
+    /// 
+    /// Default: modp
+    pub group: GroupType,
+    /// A well-known group from RFC 3526 (`modp2048` to `modp8192`) or RFC 7919 (`ffdhe2048` to `ffdhe8192`).
+    /// If set, the `group`, `bit_size` and `fixed_parameters` settings are ignored.
+    /// 
+    /// Default: None
+    pub named_group: Option<NamedGroup>,
+    /// The file from which the server loads the parameters of the group.
+    /// If the file does not exist, the server generates the parameters and saves them there,
+    /// so that registrations survive restarts and several servers can share the same group.
+    /// The group of an existing file replaces the configured one, the server logs if they differ.
+    /// Files ending with `.pem` are written in a PEM-style encoding, all others as JSON.
+    /// 
+    /// Default: None
+    pub parameters_file: Option<PathBuf>,
+    /// The number of seconds a non-interactive proof is accepted after (or before) its timestamp.
+    /// This bounds the clock skew between client and server and the window in which a captured proof can be replayed.
+    /// 
+    /// Default: 30
+    pub proof_validity: u64,
+    /// The minimum bit size of the prime p of the multiplicative group.
+    /// The server refuses to start and the client refuses to authenticate with smaller groups.
+    /// It does not apply to `ristretto255`, whose order is fixed at about 2^252.
+    /// The built-in 256-bit prime is only meant for tests, which have to lower this to 255 to use it.
+    /// 
+    /// Default: 2048
+    pub minimum_bit_size: u16,
+    /// The number of seconds in which a challenge has to be solved.
+    /// 
+    /// Default: 60
+    pub challenge_ttl: u64,
+    /// The number of seconds a session is valid after it was created or refreshed.
+    /// 
+    /// Default: 3600
+    pub session_ttl: u64,
+    /// Whether sessions are handed out as JWTs signed with Ed25519 instead of plain session ids.
+    /// The tokens carry the user (`sub`), the session id (`sid`), the issue time (`iat`) and the expiry (`exp`),
+    /// so that other services can verify them offline with the key published by `GetSessionKey`.
+    /// The server still checks the stored session, so that logged out sessions are rejected.
+    /// 
+    /// Default: false
+    pub session_tokens: bool,
+    /// The PKCS#8 PEM file from which the server loads the key which signs the session tokens.
+    /// If the file does not exist, the server generates the key and saves it there, so that tokens survive restarts.
+    /// If not set, a new key is generated at every start.
+    /// 
+    /// Default: None
+    pub session_key_file: Option<PathBuf>,
+    /// The bearer token with which admins can delete any user through `Unregister` without a proof.
+    /// The server refuses to start if it is shorter than 32 characters, so that it can not be guessed.
+    /// If not set, users can only delete themselves.
+    /// 
+    /// Default: None
+    pub admin_token: Option<String>,
+    /// The number of failed proofs for a user after which it is locked out.
+    /// Every further failure doubles the lockout, starting with `base_lockout` up to `max_lockout` seconds.
+    /// Clients which are locked out receive RESOURCE_EXHAUSTED with a RetryInfo. 0 disables the limit.
+    /// 
+    /// Default: 5
+    pub user_failure_limit: u32,
+    /// The number of failed proofs and wrong admin tokens from one client address after which it is locked out like a user.
+    /// The counter is not reset by successful proofs. 0 disables the limit.
+    /// 
+    /// Default: 20
+    pub peer_failure_limit: u32,
+    /// The number of seconds of the first lockout.
+    /// 
+    /// Default: 1
+    pub base_lockout: u64,
+    /// The maximum number of seconds of a lockout. The failures are forgotten once none happened for that long.
+    /// 
+    /// Default: 900
+    pub max_lockout: u64,
+    /// The number of challenges a user can have open at the same time, e.g. while logging in on several devices.
+    /// If a user creates more, the ones which expire first are removed. Must be at least 1.
+    /// 
+    /// Default: 5
+    pub max_challenges_per_user: usize,
+    /// The number of sessions a user can have active at the same time.
+    /// If a user creates more, the ones which expire first are ended. Must be at least 1.
+    /// 
+    /// Default: 10
+    pub max_sessions_per_user: usize,
+    /// Whether `Register` fails with ALREADY_EXISTS for names which are taken.
+    /// If false, it succeeds without changing the user, so that registrations do not reveal which users exist.
+    /// 
+    /// Default: true
+    pub reveal_existing_users: bool,
+    /// The secret from which the server derives the salts it returns for unknown users.
+    /// Unknown users get plausible salts and challenges and only fail at the proof, like users with a wrong password.
+    /// Servers sharing a store should share the secret, so that the salt of an unknown user is the same on all of them.
+    /// If not set, a random secret is generated at every start.
+    /// 
+    /// Default: None
+    pub decoy_secret: Option<String>,
+    /// The number of seconds between two runs of the background task which removes expired challenges and sessions.
+    /// The task only runs if the server is built inside a tokio runtime. 0 disables it.
+    /// 
+    /// Default: 60
+    pub cleanup_interval: u64,
+    /// The number of seconds between two checks whether the storage is reachable.
+    /// The health service reports the server as NOT_SERVING while it is not. 0 disables the checks.
+    /// 
+    /// Default: 10
+    pub health_check_interval: u64,
+    /// The database in which users, challenges and sessions are stored.
+    /// `sqlite://<path>` stores them in the SQLite database at the path, which is created and migrated at startup.
+    /// `postgres://<user>:<password>@<host>/<database>` stores them in PostgreSQL, which allows several servers to share them.
+    /// The connections to PostgreSQL are not encrypted, so it has to be reached over a trusted network.
+    /// If not set, everything is kept in memory and lost when the server stops.
+    /// 
+    /// Default: None
+    pub database_url: Option<String>,
+    /// The number of connections the server keeps open to PostgreSQL.
+    /// 
+    /// Default: 8
+    pub database_pool_size: usize,
+    /// The Redis server in which challenges and sessions are stored, e.g. `redis://localhost:6379`.
+    /// Redis removes them by itself when they expire, while the users stay in the store selected by `database_url`.
+    /// If not set, challenges and sessions are stored together with the users.
+    /// 
+    /// Default: None
+    pub redis_url: Option<String>,
+    /// The PEM file with the certificate chain the server presents to its clients.
+    /// The client presents it to the server instead, if the server requires client certificates.
+    /// Requires `tls_key`.
+    /// 
+    /// Default: None
+    pub tls_cert: Option<PathBuf>,
+    /// The PEM file with the private key of `tls_cert`.
+    /// 
+    /// Default: None
+    pub tls_key: Option<PathBuf>,
+    /// The PEM file with the certificate authority which signs the client certificates.
+    /// If set, the server only accepts clients which present a certificate signed by it (mutual TLS).
+    /// 
+    /// Default: None
+    pub tls_client_ca: Option<PathBuf>,
+    /// The PEM file with the certificate authority the client trusts for the server certificate.
+    /// If set, the client connects with TLS and rejects servers whose certificate is not signed by it.
+    /// 
+    /// Default: None
+    pub tls_ca: Option<PathBuf>,
+    /// The name the client expects in the server certificate, if it differs from the host it connects to.
+    /// 
+    /// Default: None
+    pub tls_domain: Option<String>
 }
 
 impl Config {
-    /// Builds the configuration from the environment variables
-    pub fn build() -> Config {
-        Config {
+    /// Builds the configuration from the environment variables.
+    /// Fails if a variable can not be parsed, the message names the variable and the expected value.
+    pub fn build() -> Result<Config, Box<dyn Error>> {
+        Ok(Config {
             bit_size: env::var("BIT_SIZE")
                 .unwrap_or(String::from(DEFAULT_BIT_SIZE))
                 .parse::<u16>()
-                .expect("BIT_SIZE must be a number"),
+                .map_err(|_| "BIT_SIZE must be a number")?,
             fixed_parameters: env::var("FIXED_PARAMETERS")
                 .unwrap_or(String::from(DEFAULT_FIXED_PARAMETERS))
                 .parse::<bool>()
-                .expect("FIXED_PARAMETERS must be a boolean"),
+                .map_err(|_| "FIXED_PARAMETERS must be a boolean")?,
             port: env::var("PORT")
                 .unwrap_or(String::from(DEFAULT_PORT))
                 .parse::<u16>()
-                .expect("PORT must be a number"),
+                .map_err(|_| "PORT must be a number")?,
             host: env::var("HOST")
                 .unwrap_or(String::from(DEFAULT_HOST)),
-        }
+            group: env::var("GROUP")
+                .unwrap_or(String::from(DEFAULT_GROUP))
+                .parse::<GroupType>()
+                .map_err(|_| "GROUP must be either modp or ristretto255")?,
+            named_group: match env::var("NAMED_GROUP").unwrap_or(String::from(DEFAULT_NAMED_GROUP)).as_str() {
+                "" => None,
+                name => Some(name.parse::<NamedGroup>().map_err(|_| "NAMED_GROUP must be one of modp2048-modp8192 or ffdhe2048-ffdhe8192")?)
+            },
+            parameters_file: match env::var("PARAMETERS_FILE").unwrap_or(String::from(DEFAULT_PARAMETERS_FILE)).as_str() {
+                "" => None,
+                path => Some(PathBuf::from(path))
+            },
+            proof_validity: env::var("PROOF_VALIDITY")
+                .unwrap_or(String::from(DEFAULT_PROOF_VALIDITY))
+                .parse::<u64>()
+                .map_err(|_| "PROOF_VALIDITY must be a number")?,
+            minimum_bit_size: env::var("MINIMUM_BIT_SIZE")
+                .unwrap_or(String::from(DEFAULT_MINIMUM_BIT_SIZE))
+                .parse::<u16>()
+                .map_err(|_| "MINIMUM_BIT_SIZE must be a number")?,
+            challenge_ttl: env::var("CHALLENGE_TTL")
+                .unwrap_or(String::from(DEFAULT_CHALLENGE_TTL))
+                .parse::<u64>()
+                .map_err(|_| "CHALLENGE_TTL must be a number")?,
+            session_ttl: env::var("SESSION_TTL")
+                .unwrap_or(String::from(DEFAULT_SESSION_TTL))
+                .parse::<u64>()
+                .map_err(|_| "SESSION_TTL must be a number")?,
+            session_tokens: env::var("SESSION_TOKENS")
+                .unwrap_or(String::from(DEFAULT_SESSION_TOKENS))
+                .parse::<bool>()
+                .map_err(|_| "SESSION_TOKENS must be a boolean")?,
+            session_key_file: match env::var("SESSION_KEY_FILE").unwrap_or(String::from(DEFAULT_SESSION_KEY_FILE)).as_str() {
+                "" => None,
+                path => Some(PathBuf::from(path))
+            },
+            admin_token: match env::var("ADMIN_TOKEN").unwrap_or(String::from(DEFAULT_ADMIN_TOKEN)).as_str() {
+                "" => None,
+                token => Some(token.to_string())
+            },
+            user_failure_limit: env::var("USER_FAILURE_LIMIT")
+                .unwrap_or(String::from(DEFAULT_USER_FAILURE_LIMIT))
+                .parse::<u32>()
+                .map_err(|_| "USER_FAILURE_LIMIT must be a number")?,
+            peer_failure_limit: env::var("PEER_FAILURE_LIMIT")
+                .unwrap_or(String::from(DEFAULT_PEER_FAILURE_LIMIT))
+                .parse::<u32>()
+                .map_err(|_| "PEER_FAILURE_LIMIT must be a number")?,
+            base_lockout: env::var("BASE_LOCKOUT")
+                .unwrap_or(String::from(DEFAULT_BASE_LOCKOUT))
+                .parse::<u64>()
+                .map_err(|_| "BASE_LOCKOUT must be a number")?,
+            max_lockout: env::var("MAX_LOCKOUT")
+                .unwrap_or(String::from(DEFAULT_MAX_LOCKOUT))
+                .parse::<u64>()
+                .map_err(|_| "MAX_LOCKOUT must be a number")?,
+            max_challenges_per_user: env::var("MAX_CHALLENGES_PER_USER")
+                .unwrap_or(String::from(DEFAULT_MAX_CHALLENGES_PER_USER))
+                .parse::<usize>()
+                .ok()
+                .filter(|max| *max > 0)
+                .ok_or("MAX_CHALLENGES_PER_USER must be a positive number")?,
+            max_sessions_per_user: env::var("MAX_SESSIONS_PER_USER")
+                .unwrap_or(String::from(DEFAULT_MAX_SESSIONS_PER_USER))
+                .parse::<usize>()
+                .ok()
+                .filter(|max| *max > 0)
+                .ok_or("MAX_SESSIONS_PER_USER must be a positive number")?,
+            reveal_existing_users: env::var("REVEAL_EXISTING_USERS")
+                .unwrap_or(String::from(DEFAULT_REVEAL_EXISTING_USERS))
+                .parse::<bool>()
+                .map_err(|_| "REVEAL_EXISTING_USERS must be a boolean")?,
+            decoy_secret: match env::var("DECOY_SECRET").unwrap_or(String::from(DEFAULT_DECOY_SECRET)).as_str() {
+                "" => None,
+                secret => Some(secret.to_string())
+            },
+            cleanup_interval: env::var("CLEANUP_INTERVAL")
+                .unwrap_or(String::from(DEFAULT_CLEANUP_INTERVAL))
+                .parse::<u64>()
+                .map_err(|_| "CLEANUP_INTERVAL must be a number")?,
+            health_check_interval: env::var("HEALTH_CHECK_INTERVAL")
+                .unwrap_or(String::from(DEFAULT_HEALTH_CHECK_INTERVAL))
+                .parse::<u64>()
+                .map_err(|_| "HEALTH_CHECK_INTERVAL must be a number")?,
+            database_url: match env::var("DATABASE_URL").unwrap_or(String::from(DEFAULT_DATABASE_URL)).as_str() {
+                "" => None,
+                url => Some(url.to_string())
+            },
+            database_pool_size: env::var("DATABASE_POOL_SIZE")
+                .unwrap_or(String::from(DEFAULT_DATABASE_POOL_SIZE))
+                .parse::<usize>()
+                .map_err(|_| "DATABASE_POOL_SIZE must be a number")?,
+            redis_url: match env::var("REDIS_URL").unwrap_or(String::from(DEFAULT_REDIS_URL)).as_str() {
+                "" => None,
+                url => Some(url.to_string())
+            },
+            tls_cert: env_path("TLS_CERT"),
+            tls_key: env_path("TLS_KEY"),
+            tls_client_ca: env_path("TLS_CLIENT_CA"),
+            tls_ca: env_path("TLS_CA"),
+            tls_domain: match env::var("TLS_DOMAIN").unwrap_or(String::from(DEFAULT_TLS_DOMAIN)).as_str() {
+                "" => None,
+                domain => Some(domain.to_string())
+            },
+        })
+    }
+
+    /// The url under which the client reaches the server, `https` if the client is configured for TLS
+    pub fn server_url(&self) -> String {
+        let scheme = if self.tls_ca.is_some() { "https" } else { "http" };
+        format!("{}://{}:{}", scheme, self.host, self.port)
+    }
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    match env::var(name).unwrap_or(String::from(DEFAULT_TLS_PATH)).as_str() {
+        "" => None,
+        path => Some(PathBuf::from(path))
     }
 }
 
+// The certificate and key are only usable together
+fn tls_identity(config: &Config) -> Result<Option<Identity>, Box<dyn Error>> {
+    match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => Ok(Some(Identity::from_pem(fs::read(cert)?, fs::read(key)?))),
+        (None, None) => Ok(None),
+        _ => Err("TLS_CERT and TLS_KEY must be set together".into())
+    }
+}
+
+/// Builds the TLS configuration of the server from the certificate, key and client CA of the configuration.
+/// Returns None if no certificate is configured, in which case the server is served without TLS.
+/// Fails if a file can not be read or a client CA is configured without a certificate.
+pub fn server_tls_config(config: &Config) -> Result<Option<ServerTlsConfig>, Box<dyn Error>> {
+    let Some(identity) = tls_identity(config)? else {
+        return match config.tls_client_ca {
+            Some(_) => Err("TLS_CLIENT_CA requires TLS_CERT and TLS_KEY".into()),
+            None => Ok(None)
+        };
+    };
+    let mut tls = ServerTlsConfig::new().identity(identity);
+    if let Some(client_ca) = &config.tls_client_ca {
+        tls = tls.client_ca_root(Certificate::from_pem(fs::read(client_ca)?));
+    }
+    Ok(Some(tls))
+}
+
+/// Builds the TLS configuration of the client which only trusts the configured CA.
+/// The certificate and key are presented to servers which require client certificates.
+/// Returns None if no CA is configured, in which case the client connects without TLS.
+/// Fails if a file can not be read.
+pub fn client_tls_config(config: &Config) -> Result<Option<ClientTlsConfig>, Box<dyn Error>> {
+    let Some(ca) = &config.tls_ca else {
+        return Ok(None);
+    };
+    let mut tls = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(fs::read(ca)?));
+    if let Some(identity) = tls_identity(config)? {
+        tls = tls.identity(identity);
+    }
+    if let Some(domain) = &config.tls_domain {
+        tls = tls.domain_name(domain);
+    }
+    Ok(Some(tls))
+}
+
 /// Builds the server with the given configuration.
-/// If no configuration is given, it will use the default configuration.
+/// If no configuration is given, it will build it from the environment variables.
+/// Fails if the configuration can not be built, the parameters of the group are not valid,
+/// the parameter or session key file can not be read or written or the database can not be opened.
 /// 
 /// # Example
 /// This is how the server can be started:
@@ -100,9 +473,9 @@ impl Config {
 /// # use auth_lib::bootstrap_server;
 /// use auth_lib::cp_grpc::auth_server::AuthServer;
 /// 
-/// # let mut config = Config::build();
+/// # let mut config = Config::build()?;
 /// # config.fixed_parameters = true;
-/// let server = bootstrap_server(Some(config));
+/// let server = bootstrap_server(Some(config))?;
 /// let addr: std::net::SocketAddr = "[::1]:50051".parse()?;
 /// 
 /// // This is the ready-to-use grpc server
@@ -116,44 +489,271 @@ impl Config {
 /// */
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
-pub fn bootstrap_server(config: Option<Config>) -> impl Auth {
-    let config = config.unwrap_or(Config::build());
-    let parameters = ChaumPedersenAlgorthim::find_parameters(config.bit_size, config.fixed_parameters);
-    let algorithm = Arc::new(RwLock::new(ChaumPedersenAlgorthim::new(&parameters)));
-    let data_access = Arc::new(RwLock::new(MapDataAccess::new()));
-    let validation = Arc::new(RwLock::new(ChaumPedersenValidationImpl::new(data_access.clone())));
-    let logic = Arc::new(RwLock::new(ChaumPedersenLogicImpl::new(algorithm.clone(), data_access.clone(), validation.clone())));
-    CPAuthServer::new(logic)
+pub fn bootstrap_server(config: Option<Config>) -> Result<impl Auth, Box<dyn Error>> {
+    let (server, _) = build_server(config)?;
+    Ok(server)
+}
+
+/// Builds the server like [`bootstrap_server`] and reports its health to the health service of the reporter.
+/// The server is reported as NOT_SERVING until it is built and afterwards as SERVING whenever its storage is reachable.
+/// The server is built on the blocking thread pool, so that the runtime can answer health checks in the meantime.
+/// 
+/// # Example
+/// This is how the server can be served while it starts:
+/// ```
+/// # use std::sync::Arc;
+/// # use auth_lib::Config;
+/// # use auth_lib::bootstrap_server_with_health;
+/// use auth_lib::cp_grpc::auth_server::AuthServer;
+/// use auth_lib::grpc::pending_server::PendingAuthServer;
+/// 
+/// # tokio::runtime::Runtime::new()?.block_on(async {
+/// # let mut config = Config::build()?;
+/// # config.fixed_parameters = true;
+/// let (reporter, health_service) = tonic_health::server::health_reporter();
+/// let server = Arc::new(once_cell::sync::OnceCell::new());
+/// 
+/// // Answers with UNAVAILABLE until the server is set
+/// let grpc_server = AuthServer::new(PendingAuthServer::new(server.clone()));
+/// 
+/// // The health service and grpc server are served here before the server is built
+/// let _ = server.set(bootstrap_server_with_health(Some(config), reporter).await?);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// # })?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub async fn bootstrap_server_with_health(config: Option<Config>, mut reporter: HealthReporter) -> Result<impl Auth, Box<dyn Error>> {
+    report_status(&mut reporter, ServingStatus::NotServing).await;
+    let config = match config {
+        Some(config) => config,
+        None => Config::build()?
+    };
+    let health_check_interval = config.health_check_interval;
+    let (server, data_access) = tokio::task::spawn_blocking(move || build_server(Some(config)).map_err(|error| error.to_string())).await??;
+    spawn_health_check(&data_access, reporter, health_check_interval).await;
+    Ok(server)
+}
+
+/// Builds the reflection service, which describes the services of the zkp_auth package to tools like grpcurl.
+pub fn reflection_service() -> Result<ServerReflectionServer<impl ServerReflection>, Box<dyn Error>> {
+    Ok(tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(cp_grpc::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build()?)
+}
+
+type ServerAccess = Box<dyn DataAccess + Send + Sync>;
+
+fn build_server(config: Option<Config>) -> Result<(impl Auth, Arc<ServerAccess>), Box<dyn Error>> {
+    let config = match config {
+        Some(config) => config,
+        None => Config::build()?
+    };
+    if config.admin_token.as_ref().is_some_and(|token| token.len() < MINIMUM_ADMIN_TOKEN_LENGTH) {
+        return Err(format!("ADMIN_TOKEN must have at least {} characters", MINIMUM_ADMIN_TOKEN_LENGTH).into());
+    }
+    let parameters = server_parameters(&config)?;
+    let algorithm = Arc::new(ChaumPedersenAlgorthim::new(&parameters)?);
+    let data_access = Arc::new(create_data_access(&config)?);
+    let validation = Arc::new(ChaumPedersenValidationImpl::new(algorithm.clone(), data_access.clone(), config.proof_validity));
+    let session_tokens = match (config.session_tokens, &config.session_key_file) {
+        (false, _) => None,
+        (true, Some(path)) => Some(SessionTokens::load_or_generate(path)?),
+        (true, None) => Some(SessionTokens::generate())
+    };
+    let logic = Arc::new(ChaumPedersenLogicImpl::new(algorithm.clone(), data_access.clone(), validation.clone(), LogicSettings {
+        challenge_ttl: config.challenge_ttl,
+        session_ttl: config.session_ttl,
+        proof_validity: config.proof_validity,
+        session_tokens,
+        admin_token: config.admin_token.clone(),
+        max_challenges: config.max_challenges_per_user,
+        max_sessions: config.max_sessions_per_user,
+        rate_limit: RateLimit {
+            user_limit: config.user_failure_limit,
+            peer_limit: config.peer_failure_limit,
+            base_lockout: config.base_lockout,
+            max_lockout: config.max_lockout
+        },
+        reveal_existing_users: config.reveal_existing_users,
+        decoy_secret: config.decoy_secret.clone()
+    }));
+    spawn_cleanup(&data_access, config.cleanup_interval);
+    Ok((CPAuthServer::new(logic), data_access))
+}
+
+fn create_data_access(config: &Config) -> Result<ServerAccess, Box<dyn Error>> {
+    let data_access = create_user_access(config)?;
+    match config.redis_url.as_deref() {
+        None => Ok(data_access),
+        Some(url) => Ok(Box::new(SplitDataAccess::new(data_access, RedisChallengeAccess::new(url)?)))
+    }
+}
+
+fn create_user_access(config: &Config) -> Result<ServerAccess, Box<dyn Error>> {
+    match config.database_url.as_deref() {
+        None => Ok(Box::new(MapDataAccess::new())),
+        Some(url) if url.starts_with("postgres://") || url.starts_with("postgresql://") => {
+            Ok(Box::new(PostgresDataAccess::new(url, config.database_pool_size)?))
+        },
+        Some(url) => match url.strip_prefix("sqlite://") {
+            Some(path) => Ok(Box::new(SqliteDataAccess::new(path)?)),
+            None => Err(format!("Unsupported database url '{}'", url).into())
+        }
+    }
+}
+
+// Periodically removes expired challenges and sessions until the server is dropped
+fn spawn_cleanup<Access>(data_access: &Arc<Access>, cleanup_interval: u64)
+where
+    Access: DataAccess + Send + Sync + 'static,
+{
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    if cleanup_interval == 0 {
+        return;
+    }
+    let data_access = Arc::downgrade(data_access);
+    runtime.spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(cleanup_interval));
+        loop {
+            interval.tick().await;
+            let Some(data_access) = data_access.upgrade() else {
+                break;
+            };
+            if let Err(error) = data_access.delete_expired(current_timestamp()).await {
+                eprintln!("Could not remove expired challenges and sessions: {}", error);
+            }
+        }
+    });
+}
+
+// Reports whether the storage is reachable until the server is dropped
+async fn spawn_health_check<Access>(data_access: &Arc<Access>, mut reporter: HealthReporter, health_check_interval: u64)
+where
+    Access: DataAccess + Send + Sync + 'static,
+{
+    if health_check_interval == 0 {
+        report_status(&mut reporter, ServingStatus::Serving).await;
+        return;
+    }
+    let data_access = Arc::downgrade(data_access);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(health_check_interval));
+        loop {
+            interval.tick().await;
+            let Some(data_access) = data_access.upgrade() else {
+                break;
+            };
+            let status = match data_access.ping().await {
+                Ok(()) => ServingStatus::Serving,
+                Err(error) => {
+                    eprintln!("Storage is not reachable: {}", error);
+                    ServingStatus::NotServing
+                }
+            };
+            report_status(&mut reporter, status).await;
+        }
+    });
+}
+
+// The Auth service is the only service, so the overall status of the server is the same
+async fn report_status(reporter: &mut HealthReporter, status: ServingStatus) {
+    reporter.set_service_status(AUTH_SERVICE_NAME, status).await;
+    reporter.set_service_status("", status).await;
+}
+
+// Generated parameters are only saved once they are valid. Replicas which start at the same time race to create the file,
+// the ones which lose load the parameters of the winner, so that all of them use the same group.
+fn server_parameters(config: &Config) -> Result<ChaumPedersenParameters, Box<dyn Error>> {
+    if let Some(path) = config.parameters_file.as_ref().filter(|path| !path.exists()) {
+        let parameters = configured_parameters(config)?;
+        parameters.validate(config.minimum_bit_size)?;
+        match parameters.save(path) {
+            Ok(()) => return Ok(parameters),
+            Err(EncodingError::Io(error)) if error.kind() == ErrorKind::AlreadyExists => {},
+            Err(error) => return Err(error.into())
+        }
+    }
+    let parameters = match &config.parameters_file {
+        Some(path) => {
+            let parameters = ChaumPedersenParameters::load(path)?;
+            if !matches_configuration(&parameters, config) {
+                println!("Using the parameters in {} instead of the configured group", path.display());
+            }
+            parameters
+        },
+        None => configured_parameters(config)?
+    };
+    parameters.validate(config.minimum_bit_size)?;
+    Ok(parameters)
+}
+
+// Generated parameters are random, so only their kind and size can be compared with the configuration
+fn matches_configuration(parameters: &ChaumPedersenParameters, config: &Config) -> bool {
+    match configured_named_group(config) {
+        Some(named_group) => parameters.p == named_group.parameters().p,
+        None => parameters.group == config.group && (config.group == GroupType::Ristretto255 || parameters.bit_size == config.bit_size)
+    }
+}
+
+// The named group takes precedence, fixed parameters of the RFC 7919 sizes are an alias for those groups
+fn configured_named_group(config: &Config) -> Option<NamedGroup> {
+    let fixed_group = NamedGroup::fixed(config.bit_size).filter(|_| config.fixed_parameters && config.group == GroupType::ModP);
+    config.named_group.or(fixed_group)
+}
+
+// The named group is logged, so that it is clear which one the server runs
+fn configured_parameters(config: &Config) -> Result<ChaumPedersenParameters, String> {
+    match configured_named_group(config) {
+        Some(named_group) => {
+            println!("Using the named group {}", named_group);
+            Ok(named_group.parameters())
+        },
+        None => ChaumPedersenAlgorthim::find_parameters(config.group, config.bit_size, config.fixed_parameters)
+    }
 }
 
 /// Builds the client with the given configuration.
 /// If no configuration is given, it will use the default configuration.
 /// It automatically connects to the given destination and retrieves the parameters from the server.
-pub async fn bootstrap_client<T, D>(destination: D) -> Result<Box<dyn AuthClient>, Box<dyn Error>>
+/// If the configuration has a `tls_ca`, the connection uses TLS, see [`client_tls_config`].
+/// Fails if the configuration can not be built, the connection can not be established or the parameters of the server are not valid.
+pub async fn bootstrap_client<T, D>(destination: D, config: Option<Config>) -> Result<Box<dyn AuthClient>, Box<dyn Error>>
 where
-    D: TryInto<tonic::transport::Endpoint>,
+    D: TryInto<Endpoint>,
     D::Error: Into<StdError>,
 {    
-    let mut client = cp_grpc::auth_client::AuthClient::connect(destination).await?;
+    let config = match config {
+        Some(config) => config,
+        None => Config::build()?
+    };
+    let mut endpoint: Endpoint = destination.try_into().map_err(|error| -> Box<dyn Error> { Into::<StdError>::into(error) })?;
+    if let Some(tls) = client_tls_config(&config)? {
+        endpoint = endpoint.tls_config(tls)?;
+    }
+    let mut client = cp_grpc::auth_client::AuthClient::new(endpoint.connect().await?);
         
-    let parameter_response = client.get_authentication_parameters(tonic::Request::new({})).await?;
+    let parameter_response = client.get_authentication_parameters(tonic::Request::new(())).await?;
     
     let encoded_parameters = parameter_response.into_inner();    
+    // Servers that do not advertise a group only support the multiplicative group mod p
+    let group = match encoded_parameters.group.as_str() {
+        "" => GroupType::ModP,
+        name => name.parse::<GroupType>()?
+    };
     let parameters = ChaumPedersenParameters {
         p: BigInt::from_bytes_be(Sign::Plus, &encoded_parameters.p),
         q: BigInt::from_bytes_be(Sign::Plus, &encoded_parameters.q),
         g: BigInt::from_bytes_be(Sign::Plus, &encoded_parameters.g),
         h: BigInt::from_bytes_be(Sign::Plus, &encoded_parameters.h),
-        bit_size: encoded_parameters.bit_size as u16
+        bit_size: encoded_parameters.bit_size as u16,
+        group,
+        seed: encoded_parameters.seed
     };
-    let algorithm = ChaumPedersenAlgorthim::new(&parameters);
+    parameters.validate(config.minimum_bit_size)?;
+    let algorithm = ChaumPedersenAlgorthim::new(&parameters)?;
     let client = CPAuthClient::new(Arc::new(tokio::sync::RwLock::new(client)), Arc::new(tokio::sync::RwLock::new(algorithm)));
     Ok(Box::new(client))
-}
-
-#[doc(hidden)]
-pub fn calculate_hash<T: Hash>(t: &T) -> BigInt {
-    let mut s = FxHasher::default();
-    t.hash(&mut s);
-    s.finish().to_bigint().unwrap()
 }
\ No newline at end of file
@@ -1,3 +1,13 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 pub mod chaum_pedersen_logic;
 pub mod chaum_pedersen_model;
 pub mod chaum_pedesen_validation;
+pub mod session_token;
+pub mod rate_limit;
+pub mod decoy;
+
+// The current unix timestamp in seconds, used for the expiry of challenges and sessions
+pub fn current_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
\ No newline at end of file
@@ -1,18 +1,59 @@
-use std::sync::{Arc, RwLock};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
 
 use crate::chaum_pedersen::algorithm::{ChaumPedersen};
+use crate::chaum_pedersen::secret::SecretParameters;
 use crate::data_access::access::DataAccess;
+use crate::data_access::model::{User, Challenge, Session};
 
+use num_bigint::BigInt;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use super::chaum_pedersen_model::Parameters;
-use super::{chaum_pedersen_model::{UserRegistration, ValidationErrors, UserChallengeRequest, UserChallengeResponse, UserSolution, SessionResponse}, chaum_pedesen_validation::ChaumPedersenValidation};
+use super::current_timestamp;
+use super::chaum_pedersen_model::{Parameters, SessionKey};
+use super::decoy::Decoy;
+use super::rate_limit::RateLimit;
+use super::session_token::{SessionClaims, SessionTokens};
+use super::{chaum_pedersen_model::{UserRegistration, AuthError, UserChallengeRequest, UserChallengeResponse, UserSolution, UserSecretChange, UserProof, SessionResponse, UserSession, SessionInfo}, chaum_pedesen_validation::ChaumPedersenValidation};
 
+#[async_trait]
 pub trait ChaumPedersenLogic {
-    fn get_parameters(&self) -> Result<Parameters, ValidationErrors>;
-    fn register_user(&self, user: &UserRegistration) -> Result<(), ValidationErrors>;
-    fn authentication_challenge(&self, challenge: &UserChallengeRequest) -> Result<UserChallengeResponse, ValidationErrors>;
-    fn solve_challenge(&self, solution: &UserSolution) -> Result<SessionResponse, ValidationErrors>;
+    async fn get_parameters(&self) -> Result<Parameters, AuthError>;
+    async fn register_user(&self, user: &UserRegistration) -> Result<(), AuthError>;
+    async fn get_secret_parameters(&self, user: &str) -> Result<SecretParameters, AuthError>;
+    async fn authentication_challenge(&self, challenge: &UserChallengeRequest) -> Result<UserChallengeResponse, AuthError>;
+    async fn solve_challenge(&self, solution: &UserSolution) -> Result<SessionResponse, AuthError>;
+    async fn verify_non_interactive_proof(&self, proof: &UserProof) -> Result<SessionResponse, AuthError>;
+    async fn validate_session(&self, session_id: &str) -> Result<UserSession, AuthError>;
+    async fn refresh_session(&self, session_id: &str) -> Result<SessionResponse, AuthError>;
+    async fn logout(&self, session_id: &str) -> Result<(), AuthError>;
+    async fn get_session_key(&self) -> Result<SessionKey, AuthError>;
+    async fn change_secret(&self, change: &UserSecretChange) -> Result<(), AuthError>;
+    async fn unregister_user(&self, user: &str, solution: &UserSolution) -> Result<(), AuthError>;
+    async fn remove_user(&self, user: &str, admin_token: &str, peer: Option<IpAddr>) -> Result<(), AuthError>;
+    async fn list_sessions(&self, session_id: &str) -> Result<Vec<SessionInfo>, AuthError>;
+    async fn revoke_session(&self, session_id: &str, session_ref: &str) -> Result<(), AuthError>;
+}
+
+// The configurable behaviour of the logic
+pub struct LogicSettings {
+    pub challenge_ttl: u64,
+    pub session_ttl: u64,
+    // The seconds a non-interactive proof is accepted for, so it has to be remembered as long
+    pub proof_validity: u64,
+    pub session_tokens: Option<SessionTokens>,
+    pub admin_token: Option<String>,
+    // The number of open challenges and active sessions a user can have at the same time
+    pub max_challenges: usize,
+    pub max_sessions: usize,
+    pub rate_limit: RateLimit,
+    // Whether registrations of taken names fail, otherwise they succeed without changing the user
+    pub reveal_existing_users: bool,
+    // The secret from which the salts of unknown users are derived
+    pub decoy_secret: Option<String>
 }
 
 pub struct ChaumPedersenLogicImpl<Algorithm, Access, Validation> 
@@ -21,9 +62,20 @@ where
     Access: DataAccess + Send + Sync + 'static,
     Validation: ChaumPedersenValidation + Send + Sync + 'static 
 {
-    algorithm: Arc<RwLock<Algorithm>>,
-    data_access: Arc<RwLock<Access>>,
-    validation: Arc<RwLock<Validation>>
+    algorithm: Arc<Algorithm>,
+    data_access: Arc<Access>,
+    validation: Arc<Validation>,
+    challenge_ttl: u64,
+    session_ttl: u64,
+    // The seconds a non-interactive proof is accepted for, so it has to be remembered as long
+    proof_validity: u64,
+    session_tokens: Option<SessionTokens>,
+    admin_token: Option<String>,
+    max_challenges: usize,
+    max_sessions: usize,
+    rate_limit: RateLimit,
+    reveal_existing_users: bool,
+    decoy: Decoy
 }
 
 impl<Algorithm, Access, Validation> ChaumPedersenLogicImpl<Algorithm, Access, Validation> 
@@ -32,83 +84,367 @@ where
     Access: DataAccess + Send + Sync + 'static,
     Validation: ChaumPedersenValidation + Send + Sync + 'static 
 {
-    pub fn new(algorithm: Arc<RwLock<Algorithm>>, data_access: Arc<RwLock<Access>>, validation: Arc<RwLock<Validation>>) -> Self {
+    pub fn new(algorithm: Arc<Algorithm>, data_access: Arc<Access>, validation: Arc<Validation>, settings: LogicSettings) -> Self {
+        let LogicSettings { challenge_ttl, session_ttl, proof_validity, session_tokens, admin_token, max_challenges, max_sessions, rate_limit, reveal_existing_users, decoy_secret } = settings;
+        let (y1, y2) = algorithm.exponentiation(&algorithm.generate_random());
+        let decoy = Decoy::new(decoy_secret.as_deref(), y1, y2);
         Self {
-            algorithm: algorithm,
-            data_access: data_access,
-            validation: validation
+            algorithm,
+            data_access,
+            validation,
+            challenge_ttl,
+            session_ttl,
+            proof_validity,
+            session_tokens,
+            admin_token,
+            max_challenges,
+            max_sessions,
+            rate_limit,
+            reveal_existing_users,
+            decoy
+        }
+    }
+
+    // Fails with the time to wait while the user or the client is locked out
+    async fn check_lockout(&self, user: &str, peer: Option<IpAddr>) -> Result<(), AuthError> {
+        self.check_counters(self.rate_limit.counters(user, peer)).await
+    }
+
+    async fn check_counters(&self, counters: Vec<(String, u32)>) -> Result<(), AuthError> {
+        let now = current_timestamp();
+        for (counter, limit) in counters {
+            let Some(failures) = self.data_access.get_failures(&counter).await? else {
+                continue;
+            };
+            if let Some(locked_until) = self.rate_limit.locked_until(&failures, limit, now).filter(|locked_until| *locked_until > now) {
+                return Err(AuthError::TooManyAttempts(locked_until - now));
+            }
+        }
+        Ok(())
+    }
+
+    // Counts failed proofs and resets the counter of the user after a successful one.
+    // The counter of the client is not reset, so that it can not be cleared with an account of its own.
+    async fn record_attempt<T: Send>(&self, user: &str, peer: Option<IpAddr>, result: Result<T, AuthError>) -> Result<T, AuthError> {
+        match &result {
+            Err(AuthError::AuthenticationFailed) => self.record_failures(self.rate_limit.counters(user, peer)).await?,
+            Ok(_) if self.rate_limit.user_limit > 0 => self.data_access.reset_failures(&RateLimit::user_counter(user)).await?,
+            _ => {}
+        }
+        result
+    }
+
+    async fn record_failures(&self, counters: Vec<(String, u32)>) -> Result<(), AuthError> {
+        let now = current_timestamp();
+        for (counter, _) in counters {
+            self.data_access.record_failure(&counter, now, self.rate_limit.counter_expiry(now)).await?;
+        }
+        Ok(())
+    }
+
+    // The user is deleted first, so that challenges created for it while its data is removed can no longer be answered
+    async fn delete_user(&self, user: &str) -> Result<bool, AuthError> {
+        let deleted = self.data_access.delete_user(user).await?;
+        self.data_access.delete_by_user(user).await?;
+        Ok(deleted)
+    }
+
+    // Stores a new session, which ends the oldest ones beyond the limit of the user,
+    // and hands out a signed token for it if tokens are enabled, otherwise its id
+    async fn create_session(&self, user_id: &str) -> Result<SessionResponse, AuthError> {
+        let session_id = Uuid::new_v4().to_string();
+        let issued_at = current_timestamp();
+        let expires_at = issued_at + self.session_ttl;
+        self.data_access.create_session(&Session {
+            id: session_id.clone(),
+            user_id: user_id.to_string(),
+            expires_at
+        }, self.max_sessions).await?;
+
+        let session_id = match &self.session_tokens {
+            Some(tokens) => tokens.issue(&SessionClaims {
+                sub: user_id.to_string(),
+                sid: session_id,
+                iat: issued_at,
+                exp: expires_at
+            }),
+            None => session_id
+        };
+        Ok(SessionResponse{session_id})
+    }
+
+    // Returns the user whose challenge was answered correctly
+    async fn verify_solution(&self, solution: &UserSolution) -> Result<User, AuthError> {
+        let data_access = &self.data_access;
+        // The challenge is consumed before the verification, so every challenge can only be answered once
+        let challenge = data_access.consume_auth_challenge(&solution.auth_id).await?.ok_or(AuthError::ChallengeNotFound)?;
+        // The challenge may have expired since the validation read it
+        if challenge.expires_at <= current_timestamp() {
+            return Err(AuthError::ChallengeExpired);
         }
+        self.check_lockout(&challenge.user_id, solution.peer).await?;
+
+        let result = async {
+            let (user, known) = self.user_or_decoy(&challenge.user_id).await?;
+            let valid = self.algorithm.verify(&user.y1, &user.y2, &challenge.r1, &challenge.r2, &solution.s, &challenge.c);
+            if !valid || !known {
+                return Err(AuthError::AuthenticationFailed);
+            }
+            Ok(user)
+        }.await;
+
+        self.record_attempt(&challenge.user_id, solution.peer, result).await
+    }
+
+    // Unknown users are replaced by the decoy, so that their proofs are verified and fail like wrong ones
+    async fn user_or_decoy(&self, name: &str) -> Result<(User, bool), AuthError> {
+        match self.data_access.get_user(name).await? {
+            Some(user) => Ok((user, true)),
+            None => Ok((self.decoy.user(name), false))
+        }
+    }
+
+    // Tokens carry the id of the stored session, which is still checked so that sessions can be revoked
+    fn stored_session_id(&self, session: &str) -> Result<String, AuthError> {
+        match &self.session_tokens {
+            Some(tokens) if !session.is_empty() => Ok(tokens.verify(session, current_timestamp())?.sid),
+            _ => Ok(session.to_string())
+        }
+    }
+
+    // Returns the active sessions of the user of the session, which has to be valid itself
+    async fn user_sessions(&self, session_id: &str) -> Result<Vec<Session>, AuthError> {
+        let data_access = &self.data_access;
+        let session = data_access.get_session(session_id).await?.ok_or(AuthError::SessionNotFound)?;
+        let now = current_timestamp();
+        let mut sessions: Vec<Session> = data_access.get_user_sessions(&session.user_id).await?.into_iter()
+            .filter(|session| session.expires_at > now)
+            .collect();
+        sessions.sort_by_key(|session| session.expires_at);
+        Ok(sessions)
     }
 }
 
+// Sessions are listed by the hash of their id, so that the list can not be used to take over the other sessions
+fn session_ref(session_id: &str) -> String {
+    hex::encode(Sha256::digest(session_id.as_bytes()))
+}
+
+#[async_trait]
 impl<Algorithm, Access, Validation> ChaumPedersenLogic for ChaumPedersenLogicImpl<Algorithm, Access, Validation> 
 where 
     Algorithm: ChaumPedersen + Send + Sync + 'static,
     Access: DataAccess + Send + Sync + 'static,
     Validation: ChaumPedersenValidation + Send + Sync + 'static
 {
-    fn get_parameters(&self) -> Result<Parameters, ValidationErrors> {
-        let algorithm = self.algorithm.read().unwrap();
-
-        let parameters = algorithm.get_parameters();
+    async fn get_parameters(&self) -> Result<Parameters, AuthError> {
+        let parameters = self.algorithm.get_parameters();
         Ok(Parameters{
             p: parameters.p.clone(),
             q: parameters.q.clone(),
             g: parameters.g.clone(),
             h: parameters.h.clone(),
-            bit_size: parameters.bit_size
+            bit_size: parameters.bit_size,
+            group: parameters.group,
+            seed: parameters.seed.clone()
         })
     }
 
-    fn authentication_challenge(&self, challenge: &UserChallengeRequest) -> Result<UserChallengeResponse, ValidationErrors> {
-        self.validation.read().unwrap().validate_user_challenge_request(&challenge)?;
+    async fn authentication_challenge(&self, challenge: &UserChallengeRequest) -> Result<UserChallengeResponse, AuthError> {
+        self.check_lockout(&challenge.user, challenge.peer).await?;
+        self.validation.validate_user_challenge_request(challenge).await?;
         
-        let c = self.algorithm.write().unwrap().generate_random();
+        let c = self.algorithm.generate_random();
         let auth_id = Uuid::new_v4().to_string();
         
-        let mut data_access = self.data_access.write().unwrap();
+        // Removes the oldest challenges beyond the limit of the user
+        self.data_access.create_auth_challenge(&Challenge {
+            id: auth_id.clone(),
+            c: c.clone(),
+            r1: challenge.r1.clone(),
+            r2: challenge.r2.clone(),
+            user_id: challenge.user.clone(),
+            expires_at: current_timestamp() + self.challenge_ttl
+        }, self.max_challenges).await?;
+        
+        Ok(UserChallengeResponse{auth_id, c})
+    }
 
-        let user = data_access.get_user(&challenge.user).unwrap();
+    async fn register_user(&self, user: &UserRegistration) -> Result<(), AuthError> {
+        // If existing users are not revealed, taken names still go through the insert, so that they take as long as new ones
+        match self.validation.validate_user_registration(user).await {
+            Err(AuthError::UserAlreadyExists) if !self.reveal_existing_users => {},
+            result => result?
+        }
 
-        if user.auth_id.is_some() {         
-            let id = user.auth_id.clone().unwrap();   
-            data_access.delete_auth_challenge(&id);
+        // The validation only rejects known names early, a concurrent registration is caught by the insert
+        if !self.data_access.create_user(&user.user, &user.y1, &user.y2, &user.secret_parameters).await? && self.reveal_existing_users {
+            return Err(AuthError::UserAlreadyExists);
         }
-        data_access.create_auth_challenge(&challenge.user, &auth_id, &c, &challenge.r1, &challenge.r2);
-        
-        Ok(UserChallengeResponse{auth_id, c})
+
+        Ok(())
+    }
+
+    async fn get_secret_parameters(&self, user: &str) -> Result<SecretParameters, AuthError> {
+        self.validation.validate_secret_parameters_request(user).await?;
+
+        let (user, _) = self.user_or_decoy(user).await?;
+
+        Ok(user.secret_parameters)
     }
 
-    fn register_user(&self, user: &UserRegistration) -> Result<(), ValidationErrors> {
-        self.validation.read().unwrap().validate_user_registration(&user)?;
+    async fn solve_challenge(&self, solution: &UserSolution) -> Result<SessionResponse, AuthError> {
+        self.validation.validate_user_solution(solution).await?;
 
-        self.data_access.write().unwrap().create_user(&user.user, &user.y1, &user.y2);
+        let user = self.verify_solution(solution).await?;
+
+        self.create_session(&user.id).await
+    }
+
+    async fn verify_non_interactive_proof(&self, proof: &UserProof) -> Result<SessionResponse, AuthError> {
+        self.check_lockout(&proof.user, proof.peer).await?;
+
+        let result = async {
+            self.validation.validate_user_proof(proof).await?;
+
+            let (user, known) = self.user_or_decoy(&proof.user).await?;
+
+            let algorithm = &self.algorithm;
+            let c = algorithm.challenge_from_transcript(&proof.user, &user.y1, &user.y2, &proof.r1, &proof.r2, &proof.timestamp.to_be_bytes());
+            let valid = algorithm.verify(&user.y1, &user.y2, &proof.r1, &proof.r2, &proof.s, &c);
+            if !valid || !known {
+                return Err(AuthError::AuthenticationFailed);
+            }
+            Ok(c)
+        }.await;
+        let c = self.record_attempt(&proof.user, proof.peer, result).await?;
+
+        // The proof is kept until its timestamp leaves the window, after which the validation rejects it anyway
+        if !self.data_access.record_proof(&proof_id(&c), proof.timestamp + self.proof_validity).await? {
+            return Err(AuthError::ProofAlreadyUsed);
+        }
+
+        self.create_session(&proof.user).await
+    }
+
+    async fn validate_session(&self, session_id: &str) -> Result<UserSession, AuthError> {
+        let session_id = &self.stored_session_id(session_id)?;
+        self.validation.validate_session(session_id).await?;
+
+        let session = self.data_access.get_session(session_id).await?.ok_or(AuthError::SessionNotFound)?;
+
+        Ok(UserSession{user: session.user_id})
+    }
+
+    // Replaces the session with a new one with a fresh expiry, so that a leaked session id can not be used forever.
+    // The old session is consumed, so concurrent refreshes of the same session only create one new session.
+    async fn refresh_session(&self, session_id: &str) -> Result<SessionResponse, AuthError> {
+        let session_id = &self.stored_session_id(session_id)?;
+        self.validation.validate_session(session_id).await?;
+
+        let data_access = &self.data_access;
+        let session = data_access.consume_session(session_id).await?.ok_or(AuthError::SessionNotFound)?;
+        if session.expires_at <= current_timestamp() {
+            return Err(AuthError::SessionExpired);
+        }
+
+        self.create_session(&session.user_id).await
+    }
+
+    async fn logout(&self, session_id: &str) -> Result<(), AuthError> {
+        let session_id = &self.stored_session_id(session_id)?;
+        self.validation.validate_session(session_id).await?;
+
+        self.data_access.consume_session(session_id).await?.ok_or(AuthError::SessionNotFound)?;
 
         Ok(())
     }
 
-    fn solve_challenge(&self, solution: &UserSolution) -> Result<SessionResponse, ValidationErrors> {
-        let validation = self.validation.read().unwrap();
-        validation.validate_user_solution(&solution)?;
-        
-        let mut data_access = self.data_access.write().unwrap();
+    async fn get_session_key(&self) -> Result<SessionKey, AuthError> {
+        let tokens = self.session_tokens.as_ref().ok_or(AuthError::SessionTokensDisabled)?;
+
+        Ok(tokens.session_key())
+    }
 
-        let challenge = data_access.get_challenge(&solution.auth_id).unwrap();
-        let user = data_access.get_user(&challenge.user_id).unwrap();
+    // The new secret only replaces the one the proof was verified against, so that of two concurrent changes
+    // authenticated with the same old secret only the first one succeeds.
+    // All sessions of the user end with the old secret, so that a leaked password no longer grants access after it was changed.
+    // Tokens are rejected with their stored session, so this also covers the tokens issued before the change.
+    async fn change_secret(&self, change: &UserSecretChange) -> Result<(), AuthError> {
+        self.validation.validate_user_secret_change(change).await?;
 
-        let result = self.algorithm.read().unwrap().verify(&user.y1, &user.y2, &challenge.r1, &challenge.r2, &solution.s, &challenge.c);
-        let user_id = challenge.user_id.clone();
-        if !result {
-            return Err(ValidationErrors::Unauthenticated);
+        let user = self.verify_solution(&change.solution).await?;
+
+        if !self.data_access.update_user_secret(&user, &change.y1, &change.y2, &change.secret_parameters).await? {
+            return Err(AuthError::AuthenticationFailed);
         }
+        self.data_access.delete_by_user(&user.id).await?;
 
-        drop(challenge);
-        drop(user);
+        Ok(())
+    }
 
-        let session_id = Uuid::new_v4().to_string();
-        data_access.create_session(&user_id, &session_id);
-        data_access.delete_auth_challenge(&solution.auth_id);
+    // A named user has to be the one the challenge was created for, so that a proof can not delete another user than intended
+    async fn unregister_user(&self, user: &str, solution: &UserSolution) -> Result<(), AuthError> {
+        self.validation.validate_user_solution(solution).await?;
 
-        Ok(SessionResponse{session_id})
+        let verified = self.verify_solution(solution).await?;
+        if !user.is_empty() && user != verified.id {
+            return Err(AuthError::AuthenticationFailed);
+        }
+
+        self.delete_user(&verified.id).await?;
+
+        Ok(())
+    }
+
+    // The tokens are compared by their hashes, so that the comparison does not reveal how much of the token is right.
+    // Wrong tokens are only counted against the client, so that guessing the token does not lock out the user.
+    async fn remove_user(&self, user: &str, admin_token: &str, peer: Option<IpAddr>) -> Result<(), AuthError> {
+        let Some(expected) = &self.admin_token else {
+            return Err(AuthError::AdminAccessDenied);
+        };
+        self.check_counters(self.rate_limit.peer_counters(peer)).await?;
+        if Sha256::digest(expected.as_bytes()) != Sha256::digest(admin_token.as_bytes()) {
+            self.record_failures(self.rate_limit.peer_counters(peer)).await?;
+            return Err(AuthError::AdminAccessDenied);
+        }
+        self.validation.validate_user_removal(user).await?;
+
+        if !self.delete_user(user).await? {
+            return Err(AuthError::UserNotFound);
+        }
+
+        Ok(())
     }
+
+    async fn list_sessions(&self, session_id: &str) -> Result<Vec<SessionInfo>, AuthError> {
+        let session_id = &self.stored_session_id(session_id)?;
+        self.validation.validate_session(session_id).await?;
+
+        let sessions = self.user_sessions(session_id).await?;
+
+        Ok(sessions.into_iter().map(|session| SessionInfo {
+            session_ref: session_ref(&session.id),
+            expires_at: session.expires_at,
+            current: session.id == *session_id
+        }).collect())
+    }
+
+    // Only sessions of the same user can be revoked, so a reference of another user is reported as unknown
+    async fn revoke_session(&self, session_id: &str, session_ref: &str) -> Result<(), AuthError> {
+        let session_id = &self.stored_session_id(session_id)?;
+        self.validation.validate_session_revocation(session_id, session_ref).await?;
+
+        let sessions = self.user_sessions(session_id).await?;
+        let revoked = sessions.iter().find(|session| self::session_ref(&session.id) == session_ref).ok_or(AuthError::SessionRefNotFound)?;
+
+        self.data_access.consume_session(&revoked.id).await?.ok_or(AuthError::SessionRefNotFound)?;
+
+        Ok(())
+    }
+}
+
+// Proofs are recorded by the hash of their challenge, which covers the whole transcript
+fn proof_id(c: &BigInt) -> String {
+    format!("{:x}", Sha256::digest(c.to_bytes_be().1))
 }
\ No newline at end of file
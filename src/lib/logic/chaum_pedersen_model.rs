@@ -1,26 +1,38 @@
+use std::fmt::{self, Display};
+use std::net::IpAddr;
+
 use num_bigint::BigInt;
 
+use crate::chaum_pedersen::group::GroupType;
+use crate::chaum_pedersen::secret::SecretParameters;
+use crate::data_access::access::StorageError;
+
 #[derive(Debug)]
 pub struct Parameters{
     pub p: BigInt,
     pub q: BigInt,
     pub g: BigInt,
     pub h: BigInt,
-    pub bit_size: u16
+    pub bit_size: u16,
+    pub group: GroupType,
+    pub seed: Vec<u8>
 }
 
 #[derive(Debug)]
 pub struct UserRegistration {
     pub user: String,
     pub y1: BigInt,
-    pub y2: BigInt
+    pub y2: BigInt,
+    pub secret_parameters: SecretParameters
 }
 
 #[derive(Debug)]
 pub struct UserChallengeRequest {
     pub user: String,
     pub r1: BigInt,
-    pub r2: BigInt
+    pub r2: BigInt,
+    // The address of the client, if known, for the rate limiting
+    pub peer: Option<IpAddr>
 }
 
 #[derive(Debug)]
@@ -32,7 +44,26 @@ pub struct UserChallengeResponse {
 #[derive(Debug)]
 pub struct UserSolution {
     pub auth_id: String,
-    pub s: BigInt
+    pub s: BigInt,
+    pub peer: Option<IpAddr>
+}
+
+#[derive(Debug)]
+pub struct UserSecretChange {
+    pub solution: UserSolution,
+    pub y1: BigInt,
+    pub y2: BigInt,
+    pub secret_parameters: SecretParameters
+}
+
+#[derive(Debug)]
+pub struct UserProof {
+    pub user: String,
+    pub r1: BigInt,
+    pub r2: BigInt,
+    pub s: BigInt,
+    pub timestamp: u64,
+    pub peer: Option<IpAddr>
 }
 
 #[derive(Debug)]
@@ -41,9 +72,84 @@ pub struct SessionResponse {
 }
 
 #[derive(Debug)]
-pub enum ValidationErrors {
-    InvalidArgument,
-    Unauthenticated,
-    NotFound,
-    AlreadyExists
+pub struct UserSession {
+    pub user: String
+}
+
+// An active session of a user, which is identified by a reference that can not be used as the session itself
+#[derive(Debug)]
+pub struct SessionInfo {
+    pub session_ref: String,
+    pub expires_at: u64,
+    // Whether the session is the one which requested the list
+    pub current: bool
+}
+
+#[derive(Debug)]
+pub struct SessionKey {
+    pub algorithm: String,
+    pub key_id: String,
+    pub public_key: Vec<u8>,
+    pub public_key_pem: String
+}
+
+// Fields are named as in the request messages
+#[derive(Debug)]
+pub enum AuthError {
+    EmptyField(&'static str),
+    NotAnElement(&'static str),
+    ScalarOutOfRange(&'static str),
+    InvalidSecretParameters,
+    UserAlreadyExists,
+    // Unknown users and failed proofs are not distinguished, so that the errors do not reveal which users exist
+    AuthenticationFailed,
+    ChallengeNotFound,
+    ChallengeExpired,
+    ProofExpired,
+    ProofAlreadyUsed,
+    SessionNotFound,
+    SessionExpired,
+    // The referenced session is not one of the active sessions of the user
+    SessionRefNotFound,
+    InvalidSessionToken,
+    SessionTokensDisabled,
+    AdminAccessDenied,
+    // Only returned to admins, which may know which users exist
+    UserNotFound,
+    // The number of seconds until the next attempt is accepted
+    TooManyAttempts(u64),
+    Storage(StorageError)
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::EmptyField(field) => write!(f, "The field {} must not be empty", field),
+            AuthError::NotAnElement(field) => write!(f, "The field {} is not an element of the group", field),
+            AuthError::ScalarOutOfRange(field) => write!(f, "The field {} is not in [1, q)", field),
+            AuthError::InvalidSecretParameters => write!(f, "The secret parameters are too weak or too expensive"),
+            AuthError::UserAlreadyExists => write!(f, "The user already exists"),
+            AuthError::AuthenticationFailed => write!(f, "Authentication failed"),
+            AuthError::ChallengeNotFound => write!(f, "The challenge does not exist or was already answered"),
+            AuthError::ChallengeExpired => write!(f, "The challenge expired"),
+            AuthError::ProofExpired => write!(f, "The timestamp of the proof is outside of the accepted window"),
+            AuthError::ProofAlreadyUsed => write!(f, "The proof was already used"),
+            AuthError::SessionNotFound => write!(f, "The session does not exist"),
+            AuthError::SessionExpired => write!(f, "The session expired"),
+            AuthError::SessionRefNotFound => write!(f, "The referenced session does not exist"),
+            AuthError::InvalidSessionToken => write!(f, "The session token is not valid"),
+            AuthError::SessionTokensDisabled => write!(f, "The server does not issue session tokens"),
+            AuthError::AdminAccessDenied => write!(f, "The admin credential is missing or wrong"),
+            AuthError::UserNotFound => write!(f, "The user does not exist"),
+            AuthError::TooManyAttempts(retry_after) => write!(f, "Too many failed attempts, retry in {} seconds", retry_after),
+            // The cause is only logged, it may contain internals of the storage
+            AuthError::Storage(_) => write!(f, "The storage is unavailable"),
+        }
+    }
+}
+
+impl From<StorageError> for AuthError {
+    fn from(error: StorageError) -> Self {
+        AuthError::Storage(error)
+    }
 }
\ No newline at end of file
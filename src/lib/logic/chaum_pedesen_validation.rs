@@ -1,90 +1,194 @@
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
-use num_traits::Zero;
+use async_trait::async_trait;
 
+use crate::chaum_pedersen::algorithm::ChaumPedersen;
 use crate::data_access::access::DataAccess;
 
-use super::chaum_pedersen_model::{UserRegistration, UserChallengeRequest, UserSolution, ValidationErrors};
+use super::current_timestamp;
+use super::chaum_pedersen_model::{UserRegistration, UserChallengeRequest, UserSolution, UserSecretChange, UserProof, AuthError};
 
+#[async_trait]
 pub trait ChaumPedersenValidation {
-    fn validate_user_registration(&self, user: &UserRegistration) -> Result<(), ValidationErrors>;
-    fn validate_user_challenge_request(&self, challenge: &UserChallengeRequest) -> Result<(), ValidationErrors>;
-    fn validate_user_solution(&self, solution: &UserSolution) -> Result<(), ValidationErrors>;
+    async fn validate_user_registration(&self, user: &UserRegistration) -> Result<(), AuthError>;
+    async fn validate_secret_parameters_request(&self, user: &str) -> Result<(), AuthError>;
+    async fn validate_user_challenge_request(&self, challenge: &UserChallengeRequest) -> Result<(), AuthError>;
+    async fn validate_user_solution(&self, solution: &UserSolution) -> Result<(), AuthError>;
+    async fn validate_user_secret_change(&self, change: &UserSecretChange) -> Result<(), AuthError>;
+    async fn validate_user_proof(&self, proof: &UserProof) -> Result<(), AuthError>;
+    async fn validate_session(&self, session_id: &str) -> Result<(), AuthError>;
+    async fn validate_user_removal(&self, user: &str) -> Result<(), AuthError>;
+    async fn validate_session_revocation(&self, session_id: &str, session_ref: &str) -> Result<(), AuthError>;
 }
 
-pub struct ChaumPedersenValidationImpl<Access>
+pub struct ChaumPedersenValidationImpl<Algorithm, Access>
 where 
+    Algorithm: ChaumPedersen + Send + Sync + 'static,
     Access: DataAccess + Send + Sync + 'static,
 {
-    data_access: Arc<RwLock<Access>>
+    algorithm: Arc<Algorithm>,
+    data_access: Arc<Access>,
+    proof_validity: u64
 }
 
-impl<Access> ChaumPedersenValidationImpl<Access> 
+impl<Algorithm, Access> ChaumPedersenValidationImpl<Algorithm, Access> 
 where 
+    Algorithm: ChaumPedersen + Send + Sync + 'static,
     Access: DataAccess + Send + Sync + 'static,
 {
-    pub fn new(data_access: Arc<RwLock<Access>>) -> Self {
+    pub fn new(algorithm: Arc<Algorithm>, data_access: Arc<Access>, proof_validity: u64) -> Self {
         Self {
-            data_access: data_access
+            algorithm,
+            data_access,
+            proof_validity
         }
     }
 }
 
-impl<Access> ChaumPedersenValidation for ChaumPedersenValidationImpl<Access>
+#[async_trait]
+impl<Algorithm, Access> ChaumPedersenValidation for ChaumPedersenValidationImpl<Algorithm, Access>
 where 
+    Algorithm: ChaumPedersen + Send + Sync + 'static,
     Access: DataAccess + Send + Sync + 'static
 {
-    fn validate_user_challenge_request(&self, challenge: &UserChallengeRequest) -> Result<(), ValidationErrors> {
-        let data_access = self.data_access.read().unwrap();
+    // Unknown users are not rejected, so that the response does not reveal which users exist
+    async fn validate_user_challenge_request(&self, challenge: &UserChallengeRequest) -> Result<(), AuthError> {
+        let algorithm = &self.algorithm;
 
         if challenge.user.is_empty() {
-            return Err(ValidationErrors::InvalidArgument);
+            return Err(AuthError::EmptyField("user"));
         }
-        if challenge.r1.is_zero() {
-            return Err(ValidationErrors::InvalidArgument);
+        if !algorithm.is_element(&challenge.r1) {
+            return Err(AuthError::NotAnElement("r1"));
         }
-        if challenge.r2.is_zero() {
-            return Err(ValidationErrors::InvalidArgument);
-        }
-
-        let user = data_access.get_user(&challenge.user);
-        if user.is_none() {
-            return Err(ValidationErrors::Unauthenticated);
+        if !algorithm.is_element(&challenge.r2) {
+            return Err(AuthError::NotAnElement("r2"));
         }
 
         Ok(())
     }
 
-    fn validate_user_registration(&self, user: &UserRegistration) -> Result<(), ValidationErrors> {
-        let data_access = self.data_access.read().unwrap();
+    async fn validate_user_registration(&self, user: &UserRegistration) -> Result<(), AuthError> {
+        let algorithm = &self.algorithm;
+        let data_access = &self.data_access;
 
         if user.user.is_empty() {
-            return Err(ValidationErrors::InvalidArgument);
+            return Err(AuthError::EmptyField("user"));
+        }
+        if !algorithm.is_element(&user.y1) {
+            return Err(AuthError::NotAnElement("y1"));
         }
-        if user.y1.is_zero() {
-            return Err(ValidationErrors::InvalidArgument);
+        if !algorithm.is_element(&user.y2) {
+            return Err(AuthError::NotAnElement("y2"));
         }
-        if user.y2.is_zero() {
-            return Err(ValidationErrors::InvalidArgument);
+        if !user.secret_parameters.is_valid() {
+            return Err(AuthError::InvalidSecretParameters);
         }
 
-        let user = data_access.get_user(&user.user);
+        let user = data_access.get_user(&user.user).await?;
         if user.is_some() {
-            return Err(ValidationErrors::AlreadyExists);
+            return Err(AuthError::UserAlreadyExists);
+        }
+
+        Ok(())
+    }
+
+    async fn validate_secret_parameters_request(&self, user: &str) -> Result<(), AuthError> {
+        if user.is_empty() {
+            return Err(AuthError::EmptyField("user"));
+        }
+
+        Ok(())
+    }
+
+    async fn validate_user_solution(&self, solution: &UserSolution) -> Result<(), AuthError> {
+        let algorithm = &self.algorithm;
+        let data_access = &self.data_access;
+        let Some(auth_challenge) = data_access.get_challenge(&solution.auth_id).await? else {
+            return Err(AuthError::ChallengeNotFound);
+        };
+        if auth_challenge.expires_at <= current_timestamp() {
+            return Err(AuthError::ChallengeExpired);
+        }
+        if !algorithm.is_scalar(&solution.s) {
+            return Err(AuthError::ScalarOutOfRange("s"));
+        }
+
+        Ok(())
+    }
+
+    async fn validate_user_secret_change(&self, change: &UserSecretChange) -> Result<(), AuthError> {
+        let algorithm = &self.algorithm;
+
+        self.validate_user_solution(&change.solution).await?;
+        if !algorithm.is_element(&change.y1) {
+            return Err(AuthError::NotAnElement("y1"));
+        }
+        if !algorithm.is_element(&change.y2) {
+            return Err(AuthError::NotAnElement("y2"));
+        }
+        if !change.secret_parameters.is_valid() {
+            return Err(AuthError::InvalidSecretParameters);
+        }
+
+        Ok(())
+    }
+
+    async fn validate_user_proof(&self, proof: &UserProof) -> Result<(), AuthError> {
+        let algorithm = &self.algorithm;
+
+        if proof.user.is_empty() {
+            return Err(AuthError::EmptyField("user"));
+        }
+        if !algorithm.is_element(&proof.r1) {
+            return Err(AuthError::NotAnElement("r1"));
+        }
+        if !algorithm.is_element(&proof.r2) {
+            return Err(AuthError::NotAnElement("r2"));
+        }
+        if !algorithm.is_scalar(&proof.s) {
+            return Err(AuthError::ScalarOutOfRange("s"));
+        }
+
+        // Only recent proofs are accepted, so that the used ones only have to be remembered for a while
+        let now = current_timestamp();
+        if now.abs_diff(proof.timestamp) > self.proof_validity {
+            return Err(AuthError::ProofExpired);
         }
 
         Ok(())
     }
 
-    fn validate_user_solution(&self, solution: &UserSolution) -> Result<(), ValidationErrors> {
-        let data_access = self.data_access.read().unwrap();
-        let auth_challenge = data_access.get_challenge(&solution.auth_id);
-        
-        if auth_challenge.is_none() {
-            return Err(ValidationErrors::Unauthenticated);
+    async fn validate_session(&self, session_id: &str) -> Result<(), AuthError> {
+        let data_access = &self.data_access;
+
+        if session_id.is_empty() {
+            return Err(AuthError::EmptyField("session_id"));
         }
-        if solution.s.is_zero() {
-            return Err(ValidationErrors::InvalidArgument);
+
+        let Some(session) = data_access.get_session(session_id).await? else {
+            return Err(AuthError::SessionNotFound);
+        };
+        if session.expires_at <= current_timestamp() {
+            return Err(AuthError::SessionExpired);
+        }
+
+        Ok(())
+    }
+
+    async fn validate_user_removal(&self, user: &str) -> Result<(), AuthError> {
+        if user.is_empty() {
+            return Err(AuthError::EmptyField("user"));
+        }
+
+        Ok(())
+    }
+
+    async fn validate_session_revocation(&self, session_id: &str, session_ref: &str) -> Result<(), AuthError> {
+        self.validate_session(session_id).await?;
+
+        if session_ref.is_empty() {
+            return Err(AuthError::EmptyField("session_ref"));
         }
 
         Ok(())
@@ -0,0 +1,49 @@
+use num_bigint::BigInt;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::chaum_pedersen::secret::{SecretParameters, SALT_LENGTH};
+use crate::data_access::model::User;
+
+// Stands in for users which do not exist, so that requests for them go through the same steps as for registered users
+// and only fail where a wrong proof would fail
+pub struct Decoy {
+    key: [u8; 32],
+    y1: BigInt,
+    y2: BigInt
+}
+
+impl Decoy {
+    // The key is derived from the configured secret, so that servers sharing it hand out the same salts.
+    // Without a secret a random key is used, so the salts only stay the same until the server restarts.
+    pub fn new(secret: Option<&str>, y1: BigInt, y2: BigInt) -> Self {
+        let key = match secret {
+            Some(secret) => Sha256::digest(secret.as_bytes()).into(),
+            None => {
+                let mut key = [0u8; 32];
+                rand::thread_rng().fill_bytes(&mut key);
+                key
+            }
+        };
+        Self { key, y1, y2 }
+    }
+
+    // The salt is derived from the name, so that repeated requests for the same unknown user get the same parameters
+    pub fn secret_parameters(&self, user: &str) -> SecretParameters {
+        let salt = Sha256::new().chain_update(self.key).chain_update(user.as_bytes()).finalize();
+        SecretParameters {
+            salt: salt[..SALT_LENGTH].to_vec(),
+            ..SecretParameters::generate()
+        }
+    }
+
+    // A user whose secret nobody knows, so that no proof for it is accepted
+    pub fn user(&self, name: &str) -> User {
+        User {
+            id: name.to_string(),
+            y1: self.y1.clone(),
+            y2: self.y2.clone(),
+            secret_parameters: self.secret_parameters(name)
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,59 @@
+use std::net::IpAddr;
+
+use crate::data_access::model::Failures;
+
+// Failed proofs are counted per user and per address of the client. Once a counter reaches its limit,
+// every further failure doubles the time in which no attempt is accepted, up to the maximum lockout.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    // A limit of 0 disables the counter
+    pub user_limit: u32,
+    pub peer_limit: u32,
+    pub base_lockout: u64,
+    pub max_lockout: u64
+}
+
+impl RateLimit {
+    pub fn user_counter(user: &str) -> String {
+        format!("user:{}", user)
+    }
+
+    pub fn peer_counter(peer: &IpAddr) -> String {
+        format!("peer:{}", peer)
+    }
+
+    // The counters which apply to an attempt together with their limits
+    pub fn counters(&self, user: &str, peer: Option<IpAddr>) -> Vec<(String, u32)> {
+        let mut counters = Vec::new();
+        if self.user_limit > 0 {
+            counters.push((Self::user_counter(user), self.user_limit));
+        }
+        counters.extend(self.peer_counters(peer));
+        counters
+    }
+
+    // Only the counter of the client, for attempts which do not prove anything about a user
+    pub fn peer_counters(&self, peer: Option<IpAddr>) -> Vec<(String, u32)> {
+        peer.filter(|_| self.peer_limit > 0)
+            .map(|peer| (Self::peer_counter(&peer), self.peer_limit))
+            .into_iter()
+            .collect()
+    }
+
+    // The unix timestamp until which no attempt is accepted, None if the counter did not reach its limit
+    pub fn locked_until(&self, failures: &Failures, limit: u32, now: u64) -> Option<u64> {
+        if failures.expires_at <= now || failures.count < limit {
+            return None;
+        }
+        let lockout = 2u64.checked_pow(failures.count - limit)
+            .and_then(|factor| self.base_lockout.checked_mul(factor))
+            .unwrap_or(u64::MAX)
+            .min(self.max_lockout);
+        Some(failures.last_failure.saturating_add(lockout))
+    }
+
+    // The counters are kept as long as the longest lockout, so that they are forgotten after a quiet period
+    pub fn counter_expiry(&self, now: u64) -> u64 {
+        now + self.max_lockout
+    }
+}
\ No newline at end of file
@@ -0,0 +1,132 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ed25519_dalek::{Signature, Signer, SigningKey};
+use ed25519_dalek::pkcs8::{DecodePrivateKey, EncodePrivateKey, EncodePublicKey};
+use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+
+use super::chaum_pedersen_model::{AuthError, SessionKey};
+
+// The JOSE name of Ed25519 signatures (RFC 8037)
+pub const TOKEN_ALGORITHM: &str = "EdDSA";
+const TOKEN_TYPE: &str = "JWT";
+
+#[derive(Serialize, Deserialize)]
+struct TokenHeader {
+    alg: String,
+    typ: String,
+    kid: String
+}
+
+// The claims of a session token, named as registered in RFC 7519 except for the session id
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionClaims {
+    pub sub: String,
+    pub sid: String,
+    pub iat: u64,
+    pub exp: u64
+}
+
+// Issues and verifies session tokens as JWTs signed with Ed25519,
+// so that other services can verify them offline with the public key
+pub struct SessionTokens {
+    signing_key: SigningKey,
+    key_id: String
+}
+
+impl SessionTokens {
+    pub fn new(signing_key: SigningKey) -> Self {
+        // Identifies the key in the token header, so that verifiers notice when it changed
+        let key_id = hex::encode(&Sha256::digest(signing_key.verifying_key().as_bytes())[..8]);
+        Self {
+            signing_key,
+            key_id
+        }
+    }
+
+    pub fn generate() -> Self {
+        SessionTokens::new(SigningKey::generate(&mut OsRng))
+    }
+
+    // Loads the key from the PKCS#8 PEM file or generates it and saves it there, so that tokens survive restarts
+    pub fn load_or_generate(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if path.exists() {
+            let signing_key = SigningKey::from_pkcs8_pem(&fs::read_to_string(path)?)?;
+            return Ok(SessionTokens::new(signing_key));
+        }
+        let tokens = SessionTokens::generate();
+        write_private(path, tokens.signing_key.to_pkcs8_pem(LineEnding::LF)?.as_bytes())?;
+        Ok(tokens)
+    }
+
+    pub fn session_key(&self) -> SessionKey {
+        let verifying_key = self.signing_key.verifying_key();
+        SessionKey {
+            algorithm: TOKEN_ALGORITHM.to_string(),
+            key_id: self.key_id.clone(),
+            public_key: verifying_key.to_bytes().to_vec(),
+            public_key_pem: verifying_key.to_public_key_pem(LineEnding::LF).expect("Ed25519 keys can always be encoded")
+        }
+    }
+
+    pub fn issue(&self, claims: &SessionClaims) -> String {
+        let header = TokenHeader {
+            alg: TOKEN_ALGORITHM.to_string(),
+            typ: TOKEN_TYPE.to_string(),
+            kid: self.key_id.clone()
+        };
+        let signing_input = format!("{}.{}", encode_part(&header), encode_part(claims));
+        let signature = self.signing_key.sign(signing_input.as_bytes());
+        format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature.to_bytes()))
+    }
+
+    // Only accepts tokens signed with the own key, the algorithm of the header is never trusted
+    pub fn verify(&self, token: &str, now: u64) -> Result<SessionClaims, AuthError> {
+        let (signing_input, signature) = token.rsplit_once('.').ok_or(AuthError::InvalidSessionToken)?;
+        let (header, claims) = signing_input.split_once('.').ok_or(AuthError::InvalidSessionToken)?;
+        let header: TokenHeader = decode_part(header)?;
+        if header.alg != TOKEN_ALGORITHM || header.kid != self.key_id {
+            return Err(AuthError::InvalidSessionToken);
+        }
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| AuthError::InvalidSessionToken)?;
+        let signature = Signature::from_slice(&signature).map_err(|_| AuthError::InvalidSessionToken)?;
+        self.signing_key.verifying_key()
+            .verify_strict(signing_input.as_bytes(), &signature)
+            .map_err(|_| AuthError::InvalidSessionToken)?;
+
+        let claims: SessionClaims = decode_part(claims)?;
+        if claims.exp <= now {
+            return Err(AuthError::SessionExpired);
+        }
+        Ok(claims)
+    }
+}
+
+fn encode_part<T: Serialize>(part: &T) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(part).expect("Strings and numbers can always be serialized"))
+}
+
+fn decode_part<T: DeserializeOwned>(part: &str) -> Result<T, AuthError> {
+    let json = URL_SAFE_NO_PAD.decode(part).map_err(|_| AuthError::InvalidSessionToken)?;
+    serde_json::from_slice(&json).map_err(|_| AuthError::InvalidSessionToken)
+}
+
+// Only the owner may read the private key
+#[cfg(unix)]
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?.write_all(content)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    fs::write(path, content)
+}
\ No newline at end of file
@@ -1,5 +1,5 @@
 trait DA {
-    fn create(&mut self, id: &str, value: &str) -> ();
+    fn create(&mut self, id: &str, value: &str);
 }
 
 struct Map {
@@ -15,7 +15,7 @@ impl Map {
 }
 
 impl DA for Map {
-    fn create(&mut self, id: &str, value: &str) -> () {
+    fn create(&mut self, id: &str, value: &str) {
         self.map.insert(String::from(id), String::from(value));
     }
 }
@@ -35,7 +35,7 @@ where D: DA + Sync + Send
         }
     }
 
-    async fn create(&mut self, id: &str, value: &str) -> () {
+    async fn create(&mut self, id: &str, value: &str) {
         self.data_access.create(id, value);
     }
 }
@@ -45,7 +45,6 @@ async fn main(){
     let mut da = Map::new();
     let mut use_case = UseCase::new(&mut da);
     use_case.create("1", "2").await;
-    drop(use_case);
     use_case = UseCase::new(&mut da);
     use_case.create("3", "4").await;
     println!("{:?}", da.map);
@@ -1,18 +1,35 @@
+use std::sync::Arc;
+
 use auth_lib::cp_grpc::auth_server::AuthServer;
-use auth_lib::{bootstrap_server, Config};
+use auth_lib::grpc::pending_server::PendingAuthServer;
+use auth_lib::{bootstrap_server_with_health, reflection_service, server_tls_config, Config};
+use once_cell::sync::OnceCell;
 use tonic::transport::Server;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config = Config::build();
+    let config = Config::build()?;
     let addr = format!("{}:{}", config.host, config.port).parse()?;
-    let auth_server = bootstrap_server(Some(config));
+    let tls = server_tls_config(&config)?;
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    let auth_server = Arc::new(OnceCell::new());
+
+    let mut builder = Server::builder();
+    if let Some(tls) = tls {
+        builder = builder.tls_config(tls)?;
+    }
 
+    // The health checks are answered while the parameters are generated, the Auth service is unavailable until then
     println!("Server listening on {}", addr);
-    Server::builder()
-        .add_service(AuthServer::new(auth_server))
-        .serve(addr)
-        .await?;
+    let serve = tokio::spawn(builder
+        .add_service(health_service)
+        .add_service(reflection_service()?)
+        .add_service(AuthServer::new(PendingAuthServer::new(auth_server.clone())))
+        .serve(addr));
+
+    let _ = auth_server.set(bootstrap_server_with_health(Some(config), health_reporter).await?);
+    println!("Server ready");
+    serve.await??;
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,41 @@
+use std::env;
+
+use auth_lib::{bootstrap_server, Config};
+
+// The environment is shared by all tests of a binary, so these tests live in their own one
+#[test]
+fn invalid_environment_variable_is_rejected() {
+    env::set_var("SESSION_TTL", "one hour");
+
+    let config = Config::build();
+    let server = bootstrap_server(None);
+
+    env::remove_var("SESSION_TTL");
+    assert_eq!(config.err().unwrap().to_string(), "SESSION_TTL must be a number");
+    assert!(server.is_err());
+}
+
+#[test]
+fn fixed_parameters_for_unknown_bit_size_are_rejected() {
+    let mut config = Config::build().unwrap();
+    config.fixed_parameters = true;
+    config.bit_size = 300;
+
+    assert!(bootstrap_server(Some(config)).is_err());
+}
+
+#[test]
+fn default_configuration_uses_built_in_parameters() {
+    let config = Config::build().unwrap();
+
+    assert!(config.fixed_parameters, "parameters are generated by default");
+    assert!(bootstrap_server(Some(config)).is_ok());
+}
+
+#[test]
+fn short_admin_token_is_rejected() {
+    let mut config = Config::build().unwrap();
+    config.admin_token = Some(String::from("too short"));
+
+    assert_eq!(bootstrap_server(Some(config)).err().unwrap().to_string(), "ADMIN_TOKEN must have at least 32 characters");
+}
\ No newline at end of file
@@ -0,0 +1,134 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use auth_lib::cp_grpc::auth_client::AuthClient;
+use auth_lib::cp_grpc::auth_server::AuthServer;
+use auth_lib::grpc::error_details::{error_reason, ErrorReason};
+use auth_lib::grpc::pending_server::PendingAuthServer;
+use auth_lib::{bootstrap_server_with_health, reflection_service, Config};
+use once_cell::sync::OnceCell;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Server};
+use tonic_health::pb::HealthCheckRequest;
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::server::HealthReporter;
+use tonic_reflection::pb::ServerReflectionRequest;
+use tonic_reflection::pb::server_reflection_client::ServerReflectionClient;
+use tonic_reflection::pb::server_reflection_request::MessageRequest;
+use tonic_reflection::pb::server_reflection_response::MessageResponse;
+
+const AUTH_SERVICE: &str = "zkp_auth.Auth";
+
+// Serves the health, reflection and pending Auth service like the server binary does
+async fn serve<Auth>(server: Arc<OnceCell<Auth>>) -> (Channel, HealthReporter)
+where
+    Auth: auth_lib::cp_grpc::auth_server::Auth
+{
+    let (reporter, health_service) = tonic_health::server::health_reporter();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address: SocketAddr = listener.local_addr().unwrap();
+
+    tokio::spawn(Server::builder()
+        .add_service(health_service)
+        .add_service(reflection_service().unwrap())
+        .add_service(AuthServer::new(PendingAuthServer::new(server)))
+        .serve_with_incoming(TcpListenerStream::new(listener)));
+    let channel = Channel::from_shared(format!("http://{}", address)).unwrap().connect().await.unwrap();
+    (channel, reporter)
+}
+
+fn test_config() -> Config {
+    let mut config = Config::build().unwrap();
+    config.fixed_parameters = true;
+    config
+}
+
+async fn health(channel: &Channel, service: &str) -> ServingStatus {
+    let response = HealthClient::new(channel.clone())
+        .check(tonic::Request::new(HealthCheckRequest{ service: service.to_string() }))
+        .await
+        .unwrap();
+    response.into_inner().status()
+}
+
+// The status is reported by a background task, so it is polled for a while
+async fn wait_for_health(channel: &Channel, service: &str, expected: ServingStatus) {
+    for _ in 0..50 {
+        if health(channel, service).await == expected {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("{} did not become {:?}", service, expected);
+}
+
+#[tokio::test]
+async fn auth_service_is_unavailable_until_built() {
+    let server = Arc::new(OnceCell::new());
+    let (channel, reporter) = serve(server.clone()).await;
+
+    let response = AuthClient::new(channel.clone()).get_authentication_parameters(tonic::Request::new(())).await;
+    let status = response.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unavailable);
+    assert_eq!(error_reason(&status), Some(ErrorReason::ServerStarting), "wrong error reason");
+
+    let _ = server.set(bootstrap_server_with_health(Some(test_config()), reporter).await.unwrap());
+    let response = AuthClient::new(channel).get_authentication_parameters(tonic::Request::new(())).await;
+
+    assert!(response.is_ok(), "server is not available after it was built");
+}
+
+#[tokio::test]
+async fn built_server_is_serving() {
+    let server = Arc::new(OnceCell::new());
+    let (channel, reporter) = serve(server.clone()).await;
+
+    let _ = server.set(bootstrap_server_with_health(Some(test_config()), reporter).await.unwrap());
+
+    wait_for_health(&channel, AUTH_SERVICE, ServingStatus::Serving).await;
+    wait_for_health(&channel, "", ServingStatus::Serving).await;
+}
+
+#[tokio::test]
+async fn failed_server_is_not_serving() {
+    let server = Arc::new(OnceCell::new());
+    let (channel, reporter) = serve(server.clone()).await;
+    let mut config = test_config();
+    config.database_url = Some(String::from("mysql://localhost/auth"));
+
+    match bootstrap_server_with_health(Some(config), reporter).await {
+        Ok(built) => {
+            let _ = server.set(built);
+            panic!("server was built with an unsupported database");
+        },
+        Err(_) => assert_eq!(health(&channel, AUTH_SERVICE).await, ServingStatus::NotServing)
+    }
+}
+
+#[tokio::test]
+async fn reflection_lists_services() {
+    let server = Arc::new(OnceCell::new());
+    let (channel, reporter) = serve(server.clone()).await;
+    let _ = server.set(bootstrap_server_with_health(Some(test_config()), reporter).await.unwrap());
+
+    let request = ServerReflectionRequest{
+        host: String::new(),
+        message_request: Some(MessageRequest::ListServices(String::new()))
+    };
+    let mut responses = ServerReflectionClient::new(channel)
+        .server_reflection_info(tokio_stream::iter(vec![request]))
+        .await
+        .unwrap()
+        .into_inner();
+    let response = responses.message().await.unwrap().unwrap();
+
+    let Some(MessageResponse::ListServicesResponse(services)) = response.message_response else {
+        panic!("no services listed");
+    };
+    let names: Vec<_> = services.service.into_iter().map(|service| service.name).collect();
+    assert!(names.contains(&String::from(AUTH_SERVICE)), "Auth service is not listed");
+    assert!(names.contains(&String::from("grpc.health.v1.Health")), "Health service is not listed");
+}
\ No newline at end of file
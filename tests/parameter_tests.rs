@@ -0,0 +1,213 @@
+use auth_lib::chaum_pedersen::algorithm::{ChaumPedersenAlgorthim, ChaumPedersenParameters, DEFAULT_GENERATOR_SEED};
+use auth_lib::chaum_pedersen::group::GroupType;
+use auth_lib::chaum_pedersen::named_group::NamedGroup;
+use auth_lib::chaum_pedersen::validation::ParameterError;
+use auth_lib::{bootstrap_server, Config};
+use auth_lib::cp_grpc::auth_server::Auth;
+use num_bigint::{BigInt, Sign};
+
+fn fixed_parameters() -> ChaumPedersenParameters {
+    ChaumPedersenAlgorthim::find_parameters(GroupType::ModP, 256, true).unwrap()
+}
+
+#[test]
+fn fixed_parameters_are_valid() {
+    assert_eq!(fixed_parameters().validate(255), Ok(()));
+}
+
+#[test]
+fn ristretto255_parameters_are_valid() {
+    assert_eq!(ChaumPedersenParameters::ristretto255().validate(2048), Ok(()));
+}
+
+#[test]
+fn too_small_group_is_rejected() {
+    assert_eq!(fixed_parameters().validate(2048), Err(ParameterError::TooSmall { bits: 255, minimum: 2048 }));
+}
+
+#[test]
+fn composite_modulus_is_rejected() {
+    let parameters = fixed_parameters();
+    let composite = ChaumPedersenParameters::with_generators(&parameters.p + 2, parameters.q, 256, GroupType::ModP, DEFAULT_GENERATOR_SEED);
+
+    assert_eq!(composite.validate(255), Err(ParameterError::NotPrime("p")));
+}
+
+#[test]
+fn modulus_without_subgroup_is_rejected() {
+    let parameters = fixed_parameters();
+    let unrelated = ChaumPedersenParameters::with_generators(parameters.p, BigInt::from(7919), 256, GroupType::ModP, DEFAULT_GENERATOR_SEED);
+
+    assert_eq!(unrelated.validate(255), Err(ParameterError::NotSafePrime));
+}
+
+#[test]
+fn generator_outside_of_subgroup_is_rejected() {
+    let mut parameters = fixed_parameters();
+    // p - 1 has order 2
+    parameters.g = &parameters.p - 1;
+
+    assert_eq!(parameters.validate(255), Err(ParameterError::InvalidGenerator("g")));
+}
+
+#[test]
+fn generator_not_derived_from_seed_is_rejected() {
+    let mut parameters = fixed_parameters();
+    parameters.h = BigInt::from(9);
+
+    assert_eq!(parameters.validate(255), Err(ParameterError::GeneratorsNotFromSeed));
+}
+
+#[test]
+fn foreign_curve_is_rejected() {
+    let mut parameters = ChaumPedersenParameters::ristretto255();
+    parameters.q = fixed_parameters().q;
+
+    assert_eq!(parameters.validate(255), Err(ParameterError::UnknownCurve));
+}
+
+#[test]
+fn server_refuses_to_start_with_too_small_group() {
+    let mut config = Config::build().unwrap();
+    config.fixed_parameters = true;
+    config.bit_size = 256;
+
+    assert!(bootstrap_server(Some(config)).is_err());
+}
+
+#[test]
+fn named_groups_are_valid() {
+    for group in NamedGroup::ALL {
+        assert_eq!(group.parameters().p.bits(), u64::from(group.bit_size()));
+    }
+    // Checking the generators of the bigger groups takes too long in debug builds
+    for group in [NamedGroup::Modp2048, NamedGroup::Ffdhe2048] {
+        assert_eq!(group.parameters().validate(2048), Ok(()));
+    }
+}
+
+#[test]
+fn named_groups_have_subgroup_of_half_order() {
+    for group in NamedGroup::ALL {
+        let parameters = group.parameters();
+        assert_eq!(parameters.q, (&parameters.p - 1) / 2, "wrong q for {}", group.name());
+    }
+}
+
+// validate skips the primality tests for the named groups, so this is the only check of the transcribed primes
+#[test]
+#[ignore = "testing the primality of the bigger groups takes minutes, CI runs it in release builds"]
+fn named_groups_are_fully_valid() {
+    for group in NamedGroup::ALL {
+        assert_eq!(group.parameters().validate_fully(2048), Ok(()), "{} is invalid", group.name());
+    }
+}
+
+#[test]
+fn named_groups_can_be_parsed() {
+    for group in NamedGroup::ALL {
+        assert_eq!(group.name().parse::<NamedGroup>(), Ok(group));
+    }
+    assert!("modp1024".parse::<NamedGroup>().is_err());
+}
+
+#[test]
+fn fixed_parameters_use_rfc_7919_groups() {
+    let parameters = ChaumPedersenAlgorthim::find_parameters(GroupType::ModP, 3072, true).unwrap();
+
+    assert_eq!(parameters.p, NamedGroup::Ffdhe3072.parameters().p);
+}
+
+#[tokio::test]
+async fn named_group_takes_precedence_over_fixed_parameters() {
+    let mut config = Config::build().unwrap();
+    config.fixed_parameters = true;
+    config.bit_size = 3072;
+    config.named_group = Some(NamedGroup::Modp2048);
+
+    let server = bootstrap_server(Some(config)).unwrap();
+    let served = server.get_authentication_parameters(tonic::Request::new(())).await.unwrap().into_inner();
+
+    assert_eq!(BigInt::from_bytes_be(Sign::Plus, &served.p), NamedGroup::Modp2048.parameters().p);
+}
+
+#[test]
+fn server_starts_with_named_group() {
+    let mut config = Config::build().unwrap();
+    config.named_group = Some(NamedGroup::Modp2048);
+
+    assert!(bootstrap_server(Some(config)).is_ok());
+}
+
+#[test]
+fn parameters_survive_json_roundtrip() {
+    let parameters = fixed_parameters();
+
+    assert_eq!(ChaumPedersenParameters::from_json(&parameters.to_json()).unwrap(), parameters);
+}
+
+#[test]
+fn parameters_survive_pem_roundtrip() {
+    let parameters = ChaumPedersenParameters::ristretto255();
+
+    assert_eq!(ChaumPedersenParameters::from_pem(&parameters.to_pem()).unwrap(), parameters);
+}
+
+#[test]
+fn truncated_pem_is_rejected() {
+    let pem = fixed_parameters().to_pem();
+    let mut lines: Vec<&str> = pem.lines().collect();
+    lines.remove(1);
+
+    assert!(ChaumPedersenParameters::from_pem(&lines.join("\n")).is_err());
+}
+
+#[tokio::test]
+async fn server_saves_and_reuses_generated_parameters() {
+    for file_name in ["parameters.json", "parameters.pem"] {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join(file_name);
+        let config = || {
+            let mut config = Config::build().unwrap();
+            config.fixed_parameters = false;
+            config.bit_size = 64;
+            config.minimum_bit_size = 64;
+            config.parameters_file = Some(path.clone());
+            config
+        };
+
+        bootstrap_server(Some(config())).unwrap();
+        let saved = ChaumPedersenParameters::load(&path).unwrap();
+        let restarted = bootstrap_server(Some(config())).unwrap();
+        let served = restarted.get_authentication_parameters(tonic::Request::new(())).await.unwrap().into_inner();
+
+        assert_eq!(BigInt::from_bytes_be(Sign::Plus, &served.p), saved.p);
+        assert_eq!(BigInt::from_bytes_be(Sign::Plus, &served.h), saved.h);
+    }
+}
+
+#[test]
+fn invalid_generated_parameters_are_not_saved() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("parameters.json");
+    let mut config = Config::build().unwrap();
+    config.fixed_parameters = false;
+    config.bit_size = 64;
+    config.minimum_bit_size = 128;
+    config.parameters_file = Some(path.clone());
+
+    assert!(bootstrap_server(Some(config)).is_err());
+    assert!(!path.exists(), "invalid parameters were saved");
+}
+
+#[test]
+fn saving_does_not_replace_existing_parameters() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("parameters.json");
+    let parameters = fixed_parameters();
+    parameters.save(&path).unwrap();
+    let other = ChaumPedersenAlgorthim::find_parameters(GroupType::ModP, 64, false).unwrap();
+
+    assert!(other.save(&path).is_err(), "existing parameters were replaced");
+    assert_eq!(ChaumPedersenParameters::load(&path).unwrap().p, parameters.p);
+}
\ No newline at end of file
@@ -0,0 +1,242 @@
+#[allow(dead_code)]
+mod server;
+
+use std::env;
+
+use auth_lib::cp_grpc::{AuthenticationAnswerRequest, SessionRequest, SecretParametersRequest};
+use auth_lib::chaum_pedersen::algorithm::ChaumPedersen;
+use auth_lib::Config;
+use server::*;
+use uuid::Uuid;
+
+// These tests need a local PostgreSQL database, e.g. POSTGRES_URL=postgres://postgres@localhost/auth_test
+// They are ignored by default, CI runs them with --include-ignored.
+fn postgres_config() -> Config {
+    let url = env::var("POSTGRES_URL").expect("POSTGRES_URL must be set to run these tests");
+    let mut config = test_config();
+    config.database_url = Some(url);
+    config.database_pool_size = 2;
+    config.redis_url = None;
+    config
+}
+
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database in POSTGRES_URL"]
+async fn can_authenticate_with_postgres() {
+    let context = TestContext::with_config(postgres_config())
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{
+        session_id: context.session.unwrap()
+    })).await;
+
+    assert!(response.is_ok());
+    assert_eq!(response.unwrap().into_inner().user, context.user.unwrap().user, "session belongs to wrong user");
+}
+
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database in POSTGRES_URL"]
+async fn replicas_share_challenges() {
+    let (first_config, second_config) = (postgres_config(), postgres_config());
+    let context = TestContext::with_config(first_config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let replica = TestContext::with_config(second_config);
+    let context = TestContext {
+        server: replica.server,
+        ..context
+    };
+
+    let context = context.with_session().await;
+
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{
+        session_id: context.session.unwrap()
+    })).await;
+    assert!(response.is_ok());
+}
+
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database in POSTGRES_URL"]
+async fn replicas_share_sessions_of_several_logins() {
+    let (first_config, second_config) = (postgres_config(), postgres_config());
+    let context = TestContext::with_config(first_config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let first_session = context.session.clone().unwrap();
+    let replica = TestContext::with_config(second_config);
+    let context = TestContext {
+        server: replica.server,
+        ..context
+    };
+
+    let context = context.with_challenge().await.with_session().await;
+
+    let response = context.server.list_sessions(tonic::Request::new(SessionRequest{
+        session_id: context.session.unwrap()
+    })).await;
+    assert_eq!(response.unwrap().into_inner().sessions.len(), 2, "sessions are not shared");
+    assert!(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: first_session })).await.is_ok());
+}
+
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database in POSTGRES_URL"]
+async fn replicas_with_same_decoy_secret_agree_on_unknown_users() {
+    let (mut first_config, mut second_config) = (postgres_config(), postgres_config());
+    first_config.decoy_secret = Some(String::from("decoy"));
+    second_config.decoy_secret = Some(String::from("decoy"));
+    let context = TestContext::with_config(first_config).with_algorithm().await;
+    let replica = TestContext::with_config(second_config);
+    let request = |user: &str| tonic::Request::new(SecretParametersRequest{ user: user.to_string() });
+    let user = Uuid::new_v4().to_string();
+
+    let first = context.server.get_secret_parameters(request(&user)).await.unwrap().into_inner();
+    let second = replica.server.get_secret_parameters(request(&user)).await.unwrap().into_inner();
+
+    assert_eq!(first, second, "salts of unknown users differ between replicas");
+    let context = TestContext {
+        user: Some(TestUser {
+            user,
+            ..context.with_registered_user().await.user.unwrap()
+        }),
+        ..context
+    };
+    assert!(context.with_challenge().await.challenge.is_some());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[ignore = "needs a PostgreSQL database in POSTGRES_URL"]
+async fn challenge_can_only_be_consumed_once() {
+    let (first_config, second_config) = (postgres_config(), postgres_config());
+    let replica = TestContext::with_config(second_config);
+    let context = TestContext::with_config(first_config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let algorithm = context.algorithm.as_ref().unwrap().read().await;
+    let challenge = context.challenge.as_ref().unwrap();
+    let s = algorithm.solve_challenge(&context.user.as_ref().unwrap().x, &challenge.k, &challenge.c);
+
+    // Both replicas try to consume the challenge at the same time
+    let attempts: Vec<_> = (0..4).map(|attempt| {
+        let server = if attempt % 2 == 0 { context.server.clone() } else { replica.server.clone() };
+        let solution_request = AuthenticationAnswerRequest{
+            auth_id: challenge.auth_id.clone(),
+            s: s.to_bytes_be().1,
+        };
+        tokio::spawn(async move {
+            server.verify_authentication(tonic::Request::new(solution_request)).await
+        })
+    }).collect();
+
+    let mut sessions = 0;
+    for attempt in attempts {
+        if attempt.await.unwrap().is_ok() {
+            sessions += 1;
+        }
+    }
+    assert_eq!(sessions, 1, "challenge was consumed more than once");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[ignore = "needs a PostgreSQL database in POSTGRES_URL"]
+async fn proof_can_only_be_used_once() {
+    let (first_config, second_config) = (postgres_config(), postgres_config());
+    let context = TestContext::with_config(first_config)
+        .with_algorithm().await
+        .with_registered_user().await;
+    let replica = TestContext::with_config(second_config);
+    let proof = context.non_interactive_proof(current_timestamp()).await;
+
+    // Both replicas try to use the proof at the same time
+    let attempts: Vec<_> = (0..4).map(|attempt| {
+        let server = if attempt % 2 == 0 { context.server.clone() } else { replica.server.clone() };
+        let proof = proof.clone();
+        tokio::spawn(async move {
+            server.verify_non_interactive_authentication(tonic::Request::new(proof)).await
+        })
+    }).collect();
+
+    let mut sessions = 0;
+    for attempt in attempts {
+        if attempt.await.unwrap().is_ok() {
+            sessions += 1;
+        }
+    }
+    assert_eq!(sessions, 1, "proof was used more than once");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[ignore = "needs a PostgreSQL database in POSTGRES_URL"]
+async fn concurrent_logins_keep_session_limit() {
+    let (mut first_config, mut second_config) = (postgres_config(), postgres_config());
+    first_config.max_sessions_per_user = 2;
+    second_config.max_sessions_per_user = 2;
+    let context = TestContext::with_config(first_config)
+        .with_algorithm().await
+        .with_registered_user().await;
+    let replica = TestContext::with_config(second_config);
+
+    // Both replicas log the user in at the same time
+    let mut attempts = Vec::new();
+    for attempt in 0..6 {
+        let server = if attempt % 2 == 0 { context.server.clone() } else { replica.server.clone() };
+        let proof = context.non_interactive_proof(current_timestamp()).await;
+        attempts.push(tokio::spawn(async move {
+            server.verify_non_interactive_authentication(tonic::Request::new(proof)).await
+        }));
+    }
+
+    let mut session_ids = Vec::new();
+    for attempt in attempts {
+        session_ids.push(attempt.await.unwrap().unwrap().into_inner().session_id);
+    }
+
+    let mut valid = 0;
+    for session_id in session_ids {
+        if context.server.validate_session(tonic::Request::new(SessionRequest{ session_id })).await.is_ok() {
+            valid += 1;
+        }
+    }
+    assert_eq!(valid, 2, "wrong number of active sessions");
+}
+
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database in POSTGRES_URL"]
+async fn can_change_secret_with_postgres() {
+    let context = TestContext::with_config(postgres_config())
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_changed_secret(NEW_PASSWORD).await
+        .with_challenge().await
+        .with_session().await;
+
+    assert!(context.session.is_some(), "no session provided");
+}
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database in POSTGRES_URL"]
+async fn replicas_share_failures() {
+    let (mut first_config, mut second_config) = (postgres_config(), postgres_config());
+    for config in [&mut first_config, &mut second_config] {
+        config.user_failure_limit = 1;
+        config.base_lockout = 60;
+    }
+    let first = TestContext::with_config(first_config)
+        .with_algorithm().await
+        .with_registered_user().await;
+    let second = TestContext::with_config(second_config);
+    let mut proof = first.non_interactive_proof(current_timestamp()).await;
+    proof.s = vec![1];
+
+    let failed = first.server.verify_non_interactive_authentication(tonic::Request::new(proof.clone())).await;
+    let response = second.server.verify_non_interactive_authentication(tonic::Request::new(proof)).await;
+
+    assert_eq!(failed.unwrap_err().code(), tonic::Code::Unauthenticated);
+    assert_eq!(response.unwrap_err().code(), tonic::Code::ResourceExhausted);
+}
\ No newline at end of file
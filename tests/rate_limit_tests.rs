@@ -0,0 +1,185 @@
+#[allow(dead_code)]
+mod server;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use auth_lib::chaum_pedersen::algorithm::ChaumPedersen;
+use auth_lib::cp_grpc::auth_client::AuthClient;
+use auth_lib::cp_grpc::auth_server::AuthServer;
+use auth_lib::cp_grpc::{AuthenticationAnswerRequest, AuthenticationChallengeRequest, NonInteractiveAuthenticationRequest, UnregisterRequest};
+use auth_lib::grpc::chaum_pedersen_server::AUTHORIZATION_METADATA_KEY;
+use auth_lib::grpc::error_details::{error_reason, retry_delay, ErrorReason};
+use auth_lib::{bootstrap_server, Config};
+use num_bigint::BigInt;
+use server::*;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Server};
+use uuid::Uuid;
+
+fn rate_limit_config(user_failure_limit: u32, peer_failure_limit: u32) -> Config {
+    let mut config = test_config();
+    config.user_failure_limit = user_failure_limit;
+    config.peer_failure_limit = peer_failure_limit;
+    config.base_lockout = 60;
+    config
+}
+
+// Answers a new challenge of the user with a wrong s
+async fn fail_proof(context: &TestContext) -> Result<(), tonic::Status> {
+    let context = context.with_challenge().await;
+    let challenge = context.challenge.unwrap();
+    let s = context.algorithm.unwrap().read().await.solve_challenge(&context.user.unwrap().x, &challenge.k, &challenge.c) + BigInt::from(1);
+    context.server.verify_authentication(tonic::Request::new(AuthenticationAnswerRequest{
+        auth_id: challenge.auth_id,
+        s: s.to_bytes_be().1
+    })).await?;
+    Ok(())
+}
+
+async fn challenge(context: &TestContext) -> Result<(), tonic::Status> {
+    let algorithm = context.algorithm.as_ref().unwrap().read().await;
+    let (r1, r2) = algorithm.exponentiation(&algorithm.generate_random());
+    context.server.create_authentication_challenge(tonic::Request::new(AuthenticationChallengeRequest{
+        user: context.user.as_ref().unwrap().user.clone(),
+        r1: r1.to_bytes_be().1,
+        r2: r2.to_bytes_be().1
+    })).await?;
+    Ok(())
+}
+
+fn expect_locked_out(result: Result<(), tonic::Status>, max_delay: u64) {
+    let status = result.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    assert_eq!(error_reason(&status), Some(ErrorReason::TooManyAttempts), "wrong error reason");
+    let delay = retry_delay(&status).expect("no retry delay");
+    assert!(delay > Duration::ZERO && delay <= Duration::from_secs(max_delay), "wrong retry delay {:?}", delay);
+}
+
+#[tokio::test]
+async fn user_is_locked_out_after_failed_proofs() {
+    let context = TestContext::with_config(rate_limit_config(2, 0))
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    assert_eq!(fail_proof(&context).await.unwrap_err().code(), tonic::Code::Unauthenticated);
+    assert_eq!(fail_proof(&context).await.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+    expect_locked_out(challenge(&context).await, 60);
+    expect_locked_out(context.server.verify_non_interactive_authentication(tonic::Request::new(
+        context.non_interactive_proof(current_timestamp()).await
+    )).await.map(|_| ()), 60);
+}
+
+#[tokio::test]
+async fn lockout_is_capped() {
+    let mut config = rate_limit_config(1, 0);
+    config.max_lockout = 5;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    assert!(fail_proof(&context).await.is_err());
+
+    expect_locked_out(challenge(&context).await, 5);
+}
+
+#[tokio::test]
+async fn successful_proof_resets_user_failures() {
+    let context = TestContext::with_config(rate_limit_config(2, 0))
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    assert!(fail_proof(&context).await.is_err());
+    let context = context.with_challenge().await.with_session().await;
+    assert!(fail_proof(&context).await.is_err());
+
+    assert!(challenge(&context).await.is_ok(), "user was locked out");
+}
+
+#[tokio::test]
+async fn unknown_users_are_locked_out_like_known_users() {
+    let context = TestContext::with_config(rate_limit_config(1, 0))
+        .with_algorithm().await
+        .with_registered_user().await;
+    let mut proof = context.non_interactive_proof(current_timestamp()).await;
+    proof.user = Uuid::new_v4().to_string();
+
+    let first = context.server.verify_non_interactive_authentication(tonic::Request::new(proof.clone())).await;
+    let second = context.server.verify_non_interactive_authentication(tonic::Request::new(proof)).await;
+
+    assert_eq!(first.unwrap_err().code(), tonic::Code::Unauthenticated);
+    expect_locked_out(second.map(|_| ()), 60);
+}
+
+#[tokio::test]
+async fn rate_limit_can_be_disabled() {
+    let context = TestContext::with_config(rate_limit_config(0, 0))
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    for _ in 0..3 {
+        assert_eq!(fail_proof(&context).await.unwrap_err().code(), tonic::Code::Unauthenticated);
+    }
+
+    assert!(challenge(&context).await.is_ok(), "user was locked out");
+}
+
+// The address of the client is only known to a server which serves a connection
+async fn serve(config: Config) -> Channel {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address: SocketAddr = listener.local_addr().unwrap();
+
+    tokio::spawn(Server::builder()
+        .add_service(AuthServer::new(bootstrap_server(Some(config)).unwrap()))
+        .serve_with_incoming(TcpListenerStream::new(listener)));
+    Channel::from_shared(format!("http://{}", address)).unwrap().connect().await.unwrap()
+}
+
+#[tokio::test]
+async fn client_is_locked_out_after_failed_proofs_for_any_user() {
+    let config = rate_limit_config(0, 2);
+    let mut client = AuthClient::new(serve(config).await);
+    let parameters = client.get_authentication_parameters(tonic::Request::new(())).await.unwrap().into_inner();
+    let proof = || NonInteractiveAuthenticationRequest{
+        user: Uuid::new_v4().to_string(),
+        r1: parameters.g.clone(),
+        r2: parameters.h.clone(),
+        s: vec![1],
+        timestamp: current_timestamp()
+    };
+
+    for _ in 0..2 {
+        let response = client.verify_non_interactive_authentication(tonic::Request::new(proof())).await;
+        assert_eq!(response.unwrap_err().code(), tonic::Code::Unauthenticated);
+    }
+    let response = client.verify_non_interactive_authentication(tonic::Request::new(proof())).await;
+
+    expect_locked_out(response.map(|_| ()), 60);
+}
+
+#[tokio::test]
+async fn client_is_locked_out_after_wrong_admin_tokens() {
+    let mut config = rate_limit_config(0, 2);
+    config.admin_token = Some(String::from(ADMIN_TOKEN));
+    let mut client = AuthClient::new(serve(config).await);
+    let unregister = |admin_token: &str| {
+        let mut request = tonic::Request::new(UnregisterRequest{
+            user: Uuid::new_v4().to_string(),
+            ..Default::default()
+        });
+        request.metadata_mut().insert(AUTHORIZATION_METADATA_KEY, format!("Bearer {}", admin_token).parse().unwrap());
+        request
+    };
+
+    for _ in 0..2 {
+        let response = client.unregister(unregister("wrong token")).await;
+        assert_eq!(response.unwrap_err().code(), tonic::Code::PermissionDenied);
+    }
+    let response = client.unregister(unregister(ADMIN_TOKEN)).await;
+
+    expect_locked_out(response.map(|_| ()), 60);
+}
+
+const ADMIN_TOKEN: &str = "My Admin Token Which Is Long Enough";
\ No newline at end of file
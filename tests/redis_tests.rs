@@ -0,0 +1,223 @@
+#[allow(dead_code)]
+mod server;
+
+use std::env;
+
+use auth_lib::cp_grpc::{AuthenticationAnswerRequest, AuthenticationChallengeRequest, SessionRequest, RevokeSessionRequest};
+use auth_lib::grpc::error_details::{error_reason, ErrorReason};
+use auth_lib::chaum_pedersen::algorithm::ChaumPedersen;
+use auth_lib::Config;
+use server::*;
+use tempfile::TempDir;
+
+// These tests need a local Redis server, e.g. REDIS_URL=redis://localhost:6379
+// They are ignored by default, CI runs them with --include-ignored.
+// The users are kept in a SQLite database, so that several servers can share them.
+fn redis_config(directory: &TempDir) -> Config {
+    let url = env::var("REDIS_URL").expect("REDIS_URL must be set to run these tests");
+    let mut config = test_config();
+    config.database_url = Some(format!("sqlite://{}", directory.path().join("auth.db").display()));
+    config.redis_url = Some(url);
+    config
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server in REDIS_URL"]
+async fn can_authenticate_with_redis() {
+    let directory = tempfile::tempdir().unwrap();
+    let context = TestContext::with_config(redis_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{
+        session_id: context.session.unwrap()
+    })).await;
+
+    assert!(response.is_ok());
+    assert_eq!(response.unwrap().into_inner().user, context.user.unwrap().user, "session belongs to wrong user");
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server in REDIS_URL"]
+async fn solving_challenge_beyond_limit_with_redis_panics() {
+    let directory = tempfile::tempdir().unwrap();
+    let mut config = redis_config(&directory);
+    config.max_challenges_per_user = 1;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let first_challenge = context.challenge.clone().unwrap();
+    let context = context.with_challenge().await;
+
+    let algorithm = context.algorithm.as_ref().unwrap().read().await;
+    let s = algorithm.solve_challenge(&context.user.as_ref().unwrap().x, &first_challenge.k, &first_challenge.c);
+    let response = context.server.verify_authentication(tonic::Request::new(AuthenticationAnswerRequest{
+        auth_id: first_challenge.auth_id.clone(),
+        s: s.to_bytes_be().1,
+    })).await;
+
+    assert_eq!(response.unwrap_err().code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server in REDIS_URL"]
+async fn expired_challenge_is_removed_by_redis() {
+    let directory = tempfile::tempdir().unwrap();
+    let mut config = redis_config(&directory);
+    config.challenge_ttl = 1;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+    let algorithm = context.algorithm.as_ref().unwrap().read().await;
+    let challenge = context.challenge.as_ref().unwrap();
+    let s = algorithm.solve_challenge(&context.user.as_ref().unwrap().x, &challenge.k, &challenge.c);
+    let response = context.server.verify_authentication(tonic::Request::new(AuthenticationAnswerRequest{
+        auth_id: challenge.auth_id.clone(),
+        s: s.to_bytes_be().1,
+    })).await;
+
+    assert_eq!(response.unwrap_err().code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[ignore = "needs a Redis server in REDIS_URL"]
+async fn redis_challenge_can_only_be_consumed_once() {
+    let directory = tempfile::tempdir().unwrap();
+    let (first_config, second_config) = (redis_config(&directory), redis_config(&directory));
+    let context = TestContext::with_config(first_config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let replica = TestContext::with_config(second_config);
+    let algorithm = context.algorithm.as_ref().unwrap().read().await;
+    let challenge = context.challenge.as_ref().unwrap();
+    let s = algorithm.solve_challenge(&context.user.as_ref().unwrap().x, &challenge.k, &challenge.c);
+
+    // Both replicas try to consume the challenge at the same time
+    let attempts: Vec<_> = (0..4).map(|attempt| {
+        let server = if attempt % 2 == 0 { context.server.clone() } else { replica.server.clone() };
+        let solution_request = AuthenticationAnswerRequest{
+            auth_id: challenge.auth_id.clone(),
+            s: s.to_bytes_be().1,
+        };
+        tokio::spawn(async move {
+            server.verify_authentication(tonic::Request::new(solution_request)).await
+        })
+    }).collect();
+
+    let mut sessions = 0;
+    for attempt in attempts {
+        if attempt.await.unwrap().is_ok() {
+            sessions += 1;
+        }
+    }
+    assert_eq!(sessions, 1, "challenge was consumed more than once");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[ignore = "needs a Redis server in REDIS_URL"]
+async fn redis_proof_can_only_be_used_once() {
+    let directory = tempfile::tempdir().unwrap();
+    let (first_config, second_config) = (redis_config(&directory), redis_config(&directory));
+    let context = TestContext::with_config(first_config)
+        .with_algorithm().await
+        .with_registered_user().await;
+    let replica = TestContext::with_config(second_config);
+    let proof = context.non_interactive_proof(current_timestamp()).await;
+
+    // Both replicas try to use the proof at the same time
+    let attempts: Vec<_> = (0..4).map(|attempt| {
+        let server = if attempt % 2 == 0 { context.server.clone() } else { replica.server.clone() };
+        let proof = proof.clone();
+        tokio::spawn(async move {
+            server.verify_non_interactive_authentication(tonic::Request::new(proof)).await
+        })
+    }).collect();
+
+    let mut sessions = 0;
+    for attempt in attempts {
+        if attempt.await.unwrap().is_ok() {
+            sessions += 1;
+        }
+    }
+    assert_eq!(sessions, 1, "proof was used more than once");
+}
+
+#[tokio::test]
+async fn unreachable_redis_is_unavailable() {
+    let mut config = test_config();
+    config.redis_url = Some(String::from("redis://127.0.0.1:1"));
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    let status = context.server.create_authentication_challenge(tonic::Request::new(AuthenticationChallengeRequest{
+        user: context.user.unwrap().user,
+        r1: vec![1],
+        r2: vec![1],
+    })).await.unwrap_err();
+
+    assert_eq!(status.code(), tonic::Code::Unavailable);
+    assert_eq!(error_reason(&status), Some(ErrorReason::StorageUnavailable));
+}
+
+#[tokio::test]
+async fn invalid_redis_url_is_rejected() {
+    let mut config = test_config();
+    config.redis_url = Some(String::from("localhost:6379"));
+
+    assert!(auth_lib::bootstrap_server(Some(config)).is_err());
+}
+#[tokio::test]
+#[ignore = "needs a Redis server in REDIS_URL"]
+async fn unregistering_user_removes_its_sessions_from_redis() {
+    let directory = tempfile::tempdir().unwrap();
+    let context = TestContext::with_config(redis_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let first_session = context.session.clone().unwrap();
+    let context = context.with_challenge().await.with_session().await.with_challenge().await;
+    let unregister_request = context.unregister_request().await;
+
+    assert!(context.server.unregister(tonic::Request::new(unregister_request)).await.is_ok());
+
+    for session_id in [first_session, context.session.unwrap()] {
+        let response = context.server.validate_session(tonic::Request::new(SessionRequest{ session_id })).await;
+        assert_eq!(response.unwrap_err().code(), tonic::Code::Unauthenticated);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server in REDIS_URL"]
+async fn can_list_and_revoke_sessions_with_redis() {
+    let directory = tempfile::tempdir().unwrap();
+    let context = TestContext::with_config(redis_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let first_session = context.session.clone().unwrap();
+    let context = context.with_challenge().await.with_session().await;
+    let second_session = context.session.clone().unwrap();
+
+    let sessions = context.server.list_sessions(tonic::Request::new(SessionRequest{ session_id: second_session.clone() })).await.unwrap().into_inner().sessions;
+    assert_eq!(sessions.len(), 2, "wrong number of sessions");
+    let other = sessions.iter().find(|session| !session.current).unwrap();
+    assert!(context.server.revoke_session(tonic::Request::new(RevokeSessionRequest{
+        session_id: second_session.clone(),
+        session_ref: other.session_ref.clone()
+    })).await.is_ok());
+
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: first_session })).await;
+    assert_eq!(response.unwrap_err().code(), tonic::Code::Unauthenticated);
+    let sessions = context.server.list_sessions(tonic::Request::new(SessionRequest{ session_id: second_session })).await.unwrap().into_inner().sessions;
+    assert_eq!(sessions.len(), 1, "revoked session is still listed");
+}
\ No newline at end of file
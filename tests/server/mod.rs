@@ -1,21 +1,31 @@
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use auth_lib::chaum_pedersen::algorithm::{ChaumPedersenParameters, ChaumPedersenAlgorthim, ChaumPedersen};
+use auth_lib::chaum_pedersen::group::GroupType;
+use auth_lib::chaum_pedersen::secret::{derive_secret, SecretParameters, MIN_MEMORY_COST, MIN_TIME_COST, MIN_PARALLELISM, MAX_MEMORY_COST, MAX_TIME_COST, MAX_PARALLELISM};
 use auth_lib::cp_grpc::auth_server::Auth;
 use auth_lib::{bootstrap_server, Config};
 use num_bigint::{BigInt, Sign};
-use auth_lib::cp_grpc::{RegisterRequest, AuthenticationChallengeRequest};
+use auth_lib::cp_grpc::{RegisterRequest, AuthenticationChallengeRequest, AuthenticationAnswerRequest, NonInteractiveAuthenticationRequest, ChangeSecretRequest, UnregisterRequest};
+use tokio::sync::RwLock;
 use uuid::Uuid;
 
+pub const PASSWORD: &str = "My Super Secret Password";
+pub const NEW_PASSWORD: &str = "My Even More Secret Password";
+
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub struct TestUser {
     pub user: String,
+    pub secret_parameters: SecretParameters,
     pub x: BigInt,
     pub y1: BigInt,
     pub y2: BigInt
 }
 
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub struct TestChallenge {
     pub c: BigInt,
     pub k: BigInt,
@@ -30,53 +40,63 @@ pub struct TestContext
     //I probably overcomplicated here with Arc<RwLock<ChaumPedersenAlgorthim>>
     pub algorithm: Option<Arc<RwLock<ChaumPedersenAlgorthim>>>,
     pub user: Option<TestUser>,
-    pub challenge: Option<TestChallenge>
+    pub challenge: Option<TestChallenge>,
+    pub session: Option<String>
 }
 
 impl TestContext
 {
     pub fn new() -> TestContext 
     {        
-        let mut config = Config::build();
-        config.fixed_parameters = true;
-        let server = bootstrap_server(Some(config));
+        TestContext::with_config(test_config())
+    }
+
+    pub fn with_config(config: Config) -> TestContext 
+    {
+        let server = bootstrap_server(Some(config)).unwrap();
         
         TestContext {
             server: Arc::new(Box::new(server)),
             algorithm: None,
             user: None,
-            challenge: None
+            challenge: None,
+            session: None
         }
     }
 
     pub async fn with_algorithm(&self) -> TestContext {
-        let encoded_parameters = self.server.get_authentication_parameters(tonic::Request::new({})).await.unwrap().into_inner();
+        let encoded_parameters = self.server.get_authentication_parameters(tonic::Request::new(())).await.unwrap().into_inner();
         let parameters = ChaumPedersenParameters {
             p: BigInt::from_bytes_be(Sign::Plus, &encoded_parameters.p),
             q: BigInt::from_bytes_be(Sign::Plus, &encoded_parameters.q),
             g: BigInt::from_bytes_be(Sign::Plus, &encoded_parameters.g),
             h: BigInt::from_bytes_be(Sign::Plus, &encoded_parameters.h),
-            bit_size: encoded_parameters.bit_size as u16
+            bit_size: encoded_parameters.bit_size as u16,
+            group: encoded_parameters.group.parse::<GroupType>().unwrap(),
+            seed: encoded_parameters.seed
         };
 
         TestContext {
             server: self.server.clone(),
-            algorithm: Some(Arc::new(RwLock::new(ChaumPedersenAlgorthim::new(&parameters)))),
+            algorithm: Some(Arc::new(RwLock::new(ChaumPedersenAlgorthim::new(&parameters).unwrap()))),
             user: self.user.clone(),
-            challenge: self.challenge.clone()
+            challenge: self.challenge.clone(),
+            session: self.session.clone()
         }
     }
 
     pub async fn with_registered_user(&self) -> TestContext {
         assert!(self.algorithm.is_some());
-        let algorithm = self.algorithm.as_ref().unwrap().read().unwrap();
+        let algorithm = self.algorithm.as_ref().unwrap().read().await;
         
-        let x = auth_lib::calculate_hash(&"My Super Secret Password".to_string());
+        let secret_parameters = test_secret_parameters();
+        let x = derive_secret(PASSWORD, &secret_parameters, &algorithm.get_parameters().q).unwrap();
         let (y1, y2) = algorithm.exponentiation(&x);
         let register_request = RegisterRequest{
             user: Uuid::new_v4().to_string(),
             y1: y1.to_bytes_be().1,
             y2: y2.to_bytes_be().1,
+            secret_parameters: Some(to_grpc_secret_parameters(&secret_parameters)),
         };
         let user = register_request.user.clone();
 
@@ -87,17 +107,19 @@ impl TestContext
             algorithm: self.algorithm.clone(),
             user: Some(TestUser{
                 user,
-                x: x,
-                y1: y1,
-                y2: y2
+                secret_parameters,
+                x,
+                y1,
+                y2
             }),
-            challenge: self.challenge.clone()
+            challenge: self.challenge.clone(),
+            session: self.session.clone()
         }
     }
 
     pub async fn with_challenge(&self) -> TestContext {
         assert!(self.algorithm.is_some());
-        let mut algorithm = self.algorithm.as_ref().unwrap().write().unwrap();
+        let algorithm = self.algorithm.as_ref().unwrap().read().await;
     
         let k = algorithm.generate_random();
         let (r1, r2) = algorithm.exponentiation(&k);
@@ -121,7 +143,160 @@ impl TestContext
                 r1: r1.clone(),
                 r2: r2.clone(),
                 auth_id: challenge.auth_id
-            })
+            }),
+            session: self.session.clone()
+        }
+    }
+
+    pub async fn with_session(&self) -> TestContext {
+        assert!(self.challenge.is_some());
+        let algorithm = self.algorithm.as_ref().unwrap().read().await;
+
+        let challenge = self.challenge.as_ref().unwrap();
+        let s = algorithm.solve_challenge(&self.user.as_ref().unwrap().x, &challenge.k, &challenge.c);
+        let solution_request = AuthenticationAnswerRequest{
+            auth_id: challenge.auth_id.clone(),
+            s: s.to_bytes_be().1,
+        };
+
+        let response = self.server.verify_authentication(tonic::Request::new(solution_request)).await;
+        let session = response.unwrap().into_inner();
+
+        TestContext {
+            server: self.server.clone(),
+            algorithm: self.algorithm.clone(),
+            user: self.user.clone(),
+            challenge: None,
+            session: Some(session.session_id)
         }
     }
+
+    // Answers the current challenge and returns the user as it is after the change
+    pub async fn change_secret_request(&self, password: &str) -> (TestUser, ChangeSecretRequest) {
+        assert!(self.challenge.is_some());
+        let algorithm = self.algorithm.as_ref().unwrap().read().await;
+        let user = self.user.as_ref().unwrap();
+
+        let challenge = self.challenge.as_ref().unwrap();
+        let s = algorithm.solve_challenge(&user.x, &challenge.k, &challenge.c);
+        let secret_parameters = test_secret_parameters();
+        let x = derive_secret(password, &secret_parameters, &algorithm.get_parameters().q).unwrap();
+        let (y1, y2) = algorithm.exponentiation(&x);
+        let change_request = ChangeSecretRequest{
+            auth_id: challenge.auth_id.clone(),
+            s: s.to_bytes_be().1,
+            y1: y1.to_bytes_be().1,
+            y2: y2.to_bytes_be().1,
+            secret_parameters: Some(to_grpc_secret_parameters(&secret_parameters))
+        };
+
+        (TestUser{
+            user: user.user.clone(),
+            secret_parameters,
+            x,
+            y1,
+            y2
+        }, change_request)
+    }
+
+    pub async fn with_changed_secret(&self, password: &str) -> TestContext {
+        let (user, change_request) = self.change_secret_request(password).await;
+
+        self.server.change_secret(tonic::Request::new(change_request)).await.unwrap();
+
+        TestContext {
+            server: self.server.clone(),
+            algorithm: self.algorithm.clone(),
+            user: Some(user),
+            challenge: None,
+            // Changing the secret ends all sessions of the user
+            session: None
+        }
+    }
+
+    // Answers the current challenge to delete the user
+    pub async fn unregister_request(&self) -> UnregisterRequest {
+        assert!(self.challenge.is_some());
+        let algorithm = self.algorithm.as_ref().unwrap().read().await;
+
+        let challenge = self.challenge.as_ref().unwrap();
+        let s = algorithm.solve_challenge(&self.user.as_ref().unwrap().x, &challenge.k, &challenge.c);
+
+        UnregisterRequest{
+            auth_id: challenge.auth_id.clone(),
+            s: s.to_bytes_be().1,
+            user: String::new()
+        }
+    }
+
+    pub async fn non_interactive_proof(&self, timestamp: u64) -> NonInteractiveAuthenticationRequest {
+        assert!(self.algorithm.is_some());
+        let algorithm = self.algorithm.as_ref().unwrap().read().await;
+        let user = self.user.as_ref().unwrap();
+
+        let k = algorithm.generate_random();
+        let (r1, r2) = algorithm.exponentiation(&k);
+        let c = algorithm.challenge_from_transcript(&user.user, &user.y1, &user.y2, &r1, &r2, &timestamp.to_be_bytes());
+        let s = algorithm.solve_challenge(&user.x, &k, &c);
+
+        NonInteractiveAuthenticationRequest{
+            user: user.user.clone(),
+            r1: r1.to_bytes_be().1,
+            r2: r2.to_bytes_be().1,
+            s: s.to_bytes_be().1,
+            timestamp
+        }
+    }
+}
+
+// The built-in 256-bit group keeps the tests fast, it is below the default minimum and has to be allowed explicitly
+pub fn test_config() -> Config {
+    let mut config = Config::build().unwrap();
+    config.fixed_parameters = true;
+    config.bit_size = 256;
+    config.minimum_bit_size = 255;
+    // The storage is chosen by every test, not by the environment of the test run
+    config.database_url = None;
+    config.redis_url = None;
+    config
+}
+
+// The cheapest costs the server accepts keep the tests fast, the salt is still random for every user
+pub fn test_secret_parameters() -> SecretParameters {
+    let mut secret_parameters = SecretParameters::generate();
+    secret_parameters.memory_cost = MIN_MEMORY_COST;
+    secret_parameters.time_cost = MIN_TIME_COST;
+    secret_parameters.parallelism = MIN_PARALLELISM;
+    secret_parameters
+}
+
+// Parameters which each fall short of one of the minimum costs
+pub fn weak_secret_parameters() -> Vec<SecretParameters> {
+    vec![
+        SecretParameters { memory_cost: MIN_MEMORY_COST - 1, ..test_secret_parameters() },
+        SecretParameters { time_cost: MIN_TIME_COST - 1, ..test_secret_parameters() },
+        SecretParameters { parallelism: MIN_PARALLELISM - 1, ..test_secret_parameters() },
+    ]
+}
+
+// Parameters which each exceed one of the maximum costs
+pub fn expensive_secret_parameters() -> Vec<SecretParameters> {
+    vec![
+        SecretParameters { memory_cost: MAX_MEMORY_COST + 1, ..test_secret_parameters() },
+        SecretParameters { time_cost: MAX_TIME_COST + 1, ..test_secret_parameters() },
+        SecretParameters { parallelism: MAX_PARALLELISM + 1, ..test_secret_parameters() },
+    ]
+}
+
+pub fn to_grpc_secret_parameters(secret_parameters: &SecretParameters) -> auth_lib::cp_grpc::SecretParameters {
+    auth_lib::cp_grpc::SecretParameters {
+        salt: secret_parameters.salt.clone(),
+        memory_cost: secret_parameters.memory_cost,
+        time_cost: secret_parameters.time_cost,
+        parallelism: secret_parameters.parallelism
+    }
+}
+
+pub fn current_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
 }
\ No newline at end of file
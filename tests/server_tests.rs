@@ -2,38 +2,64 @@ mod server;
 
 use std::fmt;
 
-use auth_lib::cp_grpc::{RegisterRequest, AuthenticationChallengeRequest, AuthenticationAnswerRequest};
+use auth_lib::cp_grpc::{RegisterRequest, AuthenticationChallengeRequest, AuthenticationAnswerRequest, SecretParametersRequest, SessionRequest, UnregisterRequest, RevokeSessionRequest};
+use auth_lib::grpc::chaum_pedersen_server::AUTHORIZATION_METADATA_KEY;
 use server::*;
 use uuid::Uuid;
 use auth_lib::chaum_pedersen::algorithm::ChaumPedersen;
+use auth_lib::chaum_pedersen::group::GroupType;
+use auth_lib::chaum_pedersen::secret::{derive_secret, SALT_LENGTH};
+use auth_lib::grpc::error_details::{error_info, error_reason, ErrorReason, ERROR_DOMAIN, FIELD_METADATA_KEY};
+use num_bigint::BigInt;
+use num_traits::One;
 
 #[tokio::test]
 async fn can_get_parameters() {
     let context = TestContext::new();
     
-    let paramters = context.server.get_authentication_parameters(tonic::Request::new({})).await;
+    let paramters = context.server.get_authentication_parameters(tonic::Request::new(())).await;
 
     assert!(paramters.is_ok());
     let paramters = paramters.unwrap().into_inner();
-    assert!(paramters.p.len() > 0, "parameters are empty");
-    assert!(paramters.q.len() > 0, "parameters are empty");
-    assert!(paramters.g.len() > 0, "parameters are empty");
-    assert!(paramters.h.len() > 0, "parameters are empty");
+    assert!(!paramters.p.is_empty(), "parameters are empty");
+    assert!(!paramters.q.is_empty(), "parameters are empty");
+    assert!(!paramters.g.is_empty(), "parameters are empty");
+    assert!(!paramters.h.is_empty(), "parameters are empty");
     assert!(paramters.bit_size > 0, "parameters are empty");
 }
 
+#[tokio::test]
+async fn generators_can_be_derived_from_seed() {
+    for group in [GroupType::ModP, GroupType::Ristretto255] {
+        let mut config = test_config();
+        config.group = group;
+        let context = TestContext::with_config(config).with_algorithm().await;
+        let locked_algorithm = context.algorithm.unwrap();
+        let algorithm = locked_algorithm.read().await;
+
+        let mut parameters = algorithm.get_parameters().clone();
+
+        assert!(!parameters.seed.is_empty(), "no seed provided");
+        assert!(parameters.verify_generators(), "generators do not match the seed");
+        parameters.h = parameters.g.clone();
+        assert!(!parameters.verify_generators(), "forged generator was accepted");
+    }
+}
+
 #[tokio::test]
 async fn can_register_user() {
     let context = TestContext::new().with_algorithm().await;
     let locked_algorithm = context.algorithm.unwrap();
-    let algorithm = locked_algorithm.read().unwrap();
+    let algorithm = locked_algorithm.read().await;
     
-    let x = auth_lib::calculate_hash(&"My Super Secret Password".to_string());
+    let secret_parameters = test_secret_parameters();
+    let x = derive_secret(PASSWORD, &secret_parameters, &algorithm.get_parameters().q).unwrap();
     let (y1, y2) = algorithm.exponentiation(&x);
     let register_request = RegisterRequest{
         user: Uuid::new_v4().to_string(),
         y1: y1.to_bytes_be().1,
         y2: y2.to_bytes_be().1,
+        secret_parameters: Some(to_grpc_secret_parameters(&secret_parameters)),
     };
 
     let response = context.server.register(tonic::Request::new(register_request)).await;
@@ -45,20 +71,43 @@ async fn can_register_user() {
 async fn register_user_twice_panics() {
     let context = TestContext::new().with_algorithm().await;
     let locked_algorithm = context.algorithm.unwrap();
-    let algorithm = locked_algorithm.read().unwrap();
+    let algorithm = locked_algorithm.read().await;
     
-    let x = auth_lib::calculate_hash(&"My Super Secret Password".to_string());
+    let secret_parameters = test_secret_parameters();
+    let x = derive_secret(PASSWORD, &secret_parameters, &algorithm.get_parameters().q).unwrap();
     let (y1, y2) = algorithm.exponentiation(&x);
     let register_request = RegisterRequest{
         user: Uuid::new_v4().to_string(),
         y1: y1.to_bytes_be().1,
         y2: y2.to_bytes_be().1,
+        secret_parameters: Some(to_grpc_secret_parameters(&secret_parameters)),
     };
 
     assert!(context.server.register(tonic::Request::new(register_request.clone())).await.is_ok());
     expect_already_exists(context.server.register(tonic::Request::new(register_request)).await)
 }
 
+#[tokio::test]
+async fn registering_taken_name_succeeds_without_change_if_users_are_not_revealed() {
+    let mut config = test_config();
+    config.reveal_existing_users = false;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await;
+    let algorithm = context.algorithm.as_ref().unwrap().read().await;
+    let (y1, y2) = algorithm.exponentiation(&algorithm.generate_random());
+    let register_request = RegisterRequest{
+        user: context.user.as_ref().unwrap().user.clone(),
+        y1: y1.to_bytes_be().1,
+        y2: y2.to_bytes_be().1,
+        secret_parameters: Some(to_grpc_secret_parameters(&test_secret_parameters())),
+    };
+
+    assert!(context.server.register(tonic::Request::new(register_request)).await.is_ok());
+    drop(algorithm);
+    assert!(context.with_challenge().await.with_session().await.session.is_some(), "secret of the user was replaced");
+}
+
 #[tokio::test]
 async fn register_user_with_zero_password_panics() {
     let context = TestContext::new().with_algorithm().await;
@@ -67,6 +116,7 @@ async fn register_user_with_zero_password_panics() {
         user: Uuid::new_v4().to_string(),
         y1: [0x00].to_vec(),
         y2: [0x00].to_vec(),
+        secret_parameters: Some(to_grpc_secret_parameters(&test_secret_parameters())),
     };
 
     expect_invalid_argument(context.server.register(tonic::Request::new(register_request)).await)
@@ -80,6 +130,25 @@ async fn register_user_with_empty_password_panics() {
         user: Uuid::new_v4().to_string(),
         y1: [].to_vec(),
         y2: [].to_vec(),
+        secret_parameters: Some(to_grpc_secret_parameters(&test_secret_parameters())),
+    };
+
+    expect_invalid_argument(context.server.register(tonic::Request::new(register_request)).await)
+}
+
+#[tokio::test]
+async fn register_user_outside_of_subgroup_panics() {
+    let context = TestContext::new().with_algorithm().await;
+    let locked_algorithm = context.algorithm.unwrap();
+    let algorithm = locked_algorithm.read().await;
+
+    // p - 1 has order 2, so it is not an element of the subgroup of order q
+    let order_two = &algorithm.get_parameters().p - BigInt::one();
+    let register_request = RegisterRequest{
+        user: Uuid::new_v4().to_string(),
+        y1: order_two.to_bytes_be().1,
+        y2: order_two.to_bytes_be().1,
+        secret_parameters: Some(to_grpc_secret_parameters(&test_secret_parameters())),
     };
 
     expect_invalid_argument(context.server.register(tonic::Request::new(register_request)).await)
@@ -89,26 +158,154 @@ async fn register_user_with_empty_password_panics() {
 async fn register_user_with_empty_name_panics() {
     let context = TestContext::new().with_algorithm().await;
     let locked_algorithm = context.algorithm.unwrap();
-    let algorithm = locked_algorithm.read().unwrap();
+    let algorithm = locked_algorithm.read().await;
     
-    let x = auth_lib::calculate_hash(&"My Super Secret Password".to_string());
+    let secret_parameters = test_secret_parameters();
+    let x = derive_secret(PASSWORD, &secret_parameters, &algorithm.get_parameters().q).unwrap();
     let (y1, y2) = algorithm.exponentiation(&x);
     let register_request = RegisterRequest{
         user: String::from(""),
         y1: y1.to_bytes_be().1,
         y2: y2.to_bytes_be().1,
+        secret_parameters: Some(to_grpc_secret_parameters(&secret_parameters)),
+    };
+
+    expect_invalid_argument(context.server.register(tonic::Request::new(register_request)).await)
+}
+
+#[tokio::test]
+async fn register_user_with_weak_secret_parameters_panics() {
+    let context = TestContext::new().with_algorithm().await;
+    let locked_algorithm = context.algorithm.unwrap();
+    let algorithm = locked_algorithm.read().await;
+
+    let x = derive_secret(PASSWORD, &test_secret_parameters(), &algorithm.get_parameters().q).unwrap();
+    let (y1, y2) = algorithm.exponentiation(&x);
+    for secret_parameters in weak_secret_parameters() {
+        let register_request = RegisterRequest{
+            user: Uuid::new_v4().to_string(),
+            y1: y1.to_bytes_be().1,
+            y2: y2.to_bytes_be().1,
+            secret_parameters: Some(to_grpc_secret_parameters(&secret_parameters)),
+        };
+
+        expect_invalid_argument(context.server.register(tonic::Request::new(register_request)).await)
+    }
+}
+
+#[tokio::test]
+async fn register_user_with_expensive_secret_parameters_panics() {
+    let context = TestContext::new().with_algorithm().await;
+    let locked_algorithm = context.algorithm.unwrap();
+    let algorithm = locked_algorithm.read().await;
+
+    let x = derive_secret(PASSWORD, &test_secret_parameters(), &algorithm.get_parameters().q).unwrap();
+    let (y1, y2) = algorithm.exponentiation(&x);
+    for secret_parameters in expensive_secret_parameters() {
+        let register_request = RegisterRequest{
+            user: Uuid::new_v4().to_string(),
+            y1: y1.to_bytes_be().1,
+            y2: y2.to_bytes_be().1,
+            secret_parameters: Some(to_grpc_secret_parameters(&secret_parameters)),
+        };
+
+        expect_invalid_argument(context.server.register(tonic::Request::new(register_request)).await)
+    }
+}
+
+#[tokio::test]
+async fn register_user_without_secret_parameters_panics() {
+    let context = TestContext::new().with_algorithm().await;
+    let locked_algorithm = context.algorithm.unwrap();
+    let algorithm = locked_algorithm.read().await;
+    
+    let x = derive_secret(PASSWORD, &test_secret_parameters(), &algorithm.get_parameters().q).unwrap();
+    let (y1, y2) = algorithm.exponentiation(&x);
+    let register_request = RegisterRequest{
+        user: Uuid::new_v4().to_string(),
+        y1: y1.to_bytes_be().1,
+        y2: y2.to_bytes_be().1,
+        secret_parameters: None,
+    };
+
+    expect_invalid_argument(context.server.register(tonic::Request::new(register_request)).await)
+}
+
+#[tokio::test]
+async fn register_user_with_short_salt_panics() {
+    let context = TestContext::new().with_algorithm().await;
+    let locked_algorithm = context.algorithm.unwrap();
+    let algorithm = locked_algorithm.read().await;
+    
+    let mut secret_parameters = test_secret_parameters();
+    secret_parameters.salt.truncate(8);
+    let x = derive_secret(PASSWORD, &secret_parameters, &algorithm.get_parameters().q).unwrap();
+    let (y1, y2) = algorithm.exponentiation(&x);
+    let register_request = RegisterRequest{
+        user: Uuid::new_v4().to_string(),
+        y1: y1.to_bytes_be().1,
+        y2: y2.to_bytes_be().1,
+        secret_parameters: Some(to_grpc_secret_parameters(&secret_parameters)),
     };
 
     expect_invalid_argument(context.server.register(tonic::Request::new(register_request)).await)
 }
 
+#[tokio::test]
+async fn can_get_secret_parameters() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+    let user = context.user.unwrap();
+
+    let response = context.server.get_secret_parameters(tonic::Request::new(SecretParametersRequest{
+        user: user.user.clone()
+    })).await;
+
+    assert!(response.is_ok());
+    let secret_parameters = response.unwrap().into_inner();
+    assert_eq!(secret_parameters, to_grpc_secret_parameters(&user.secret_parameters), "wrong secret parameters returned");
+}
+
+#[tokio::test]
+async fn non_existing_user_gets_stable_secret_parameters() {
+    let context = TestContext::new()
+        .with_algorithm().await;
+    let user = Uuid::new_v4().to_string();
+    let request = |user: &str| tonic::Request::new(SecretParametersRequest{ user: user.to_string() });
+
+    let response = context.server.get_secret_parameters(request(&user)).await;
+
+    assert!(response.is_ok());
+    let secret_parameters = response.unwrap().into_inner();
+    assert!(secret_parameters.salt.len() >= SALT_LENGTH, "salt is too short");
+    assert_eq!(secret_parameters, context.server.get_secret_parameters(request(&user)).await.unwrap().into_inner(), "parameters of the same user differ");
+    assert_ne!(secret_parameters, context.server.get_secret_parameters(request(&Uuid::new_v4().to_string())).await.unwrap().into_inner(), "parameters of different users are equal");
+}
+
+#[tokio::test]
+async fn same_password_with_different_salts_gives_different_secrets() {
+    let context = TestContext::new()
+        .with_algorithm().await;
+    let locked_algorithm = context.algorithm.unwrap();
+    let algorithm = locked_algorithm.read().await;
+    let q = &algorithm.get_parameters().q;
+
+    let secret_parameters = test_secret_parameters();
+    let x = derive_secret(PASSWORD, &secret_parameters, q).unwrap();
+
+    assert_eq!(x, derive_secret(PASSWORD, &secret_parameters, q).unwrap(), "derivation is not deterministic");
+    assert_ne!(x, derive_secret(PASSWORD, &test_secret_parameters(), q).unwrap(), "salt is ignored");
+    assert!(x >= BigInt::one() && &x < q, "secret is not in [1, q)");
+}
+
 #[tokio::test]
 async fn can_get_challenge() {
     let context = TestContext::new()
         .with_algorithm().await
         .with_registered_user().await;
     let locked_algorithm = context.algorithm.unwrap();
-    let mut algorithm = locked_algorithm.write().unwrap();
+    let algorithm = locked_algorithm.read().await;
 
     let k = algorithm.generate_random();
     let (r1, r2) = algorithm.exponentiation(&k);
@@ -122,7 +319,7 @@ async fn can_get_challenge() {
 
     assert!(response.is_ok());
     let challenge = response.unwrap().into_inner();
-    assert!(challenge.c.len() > 0, "challenge is empty");
+    assert!(!challenge.c.is_empty(), "challenge is empty");
     assert!(!challenge.auth_id.is_empty(), "no auth id provided");
 }
 
@@ -133,7 +330,7 @@ async fn can_get_challenge_twice() {
         .with_registered_user().await
         .with_challenge().await;
     let locked_algorithm = context.algorithm.unwrap();
-    let mut algorithm = locked_algorithm.write().unwrap();
+    let algorithm = locked_algorithm.read().await;
 
     let k = algorithm.generate_random();
     let (r1, r2) = algorithm.exponentiation(&k);
@@ -147,26 +344,42 @@ async fn can_get_challenge_twice() {
 
     assert!(response.is_ok());
     let challenge = response.unwrap().into_inner();
-    assert!(challenge.c.len() > 0, "challenge is empty");
+    assert!(!challenge.c.is_empty(), "challenge is empty");
     assert!(!challenge.auth_id.is_empty(), "no auth id provided");
 }
 
 #[tokio::test]
-async fn get_challenge_with_non_existing_user_panics() {
+async fn non_existing_user_only_fails_at_verification() {
     let context = TestContext::new()
-        .with_algorithm().await;
-    let locked_algorithm = context.algorithm.unwrap();
-    let mut algorithm = locked_algorithm.write().unwrap();
+        .with_algorithm().await
+        .with_registered_user().await;
+    let unknown_user = TestContext {
+        user: Some(TestUser {
+            user: Uuid::new_v4().to_string(),
+            ..context.user.clone().unwrap()
+        }),
+        server: context.server.clone(),
+        algorithm: context.algorithm.clone(),
+        challenge: None,
+        session: None
+    }.with_challenge().await;
+    let known_user = context.with_challenge().await;
 
-    let k = algorithm.generate_random();
-    let (r1, r2) = algorithm.exponentiation(&k);
-    let challenge_request = AuthenticationChallengeRequest{
-        user: Uuid::new_v4().to_string(),
-        r1: r1.to_bytes_be().1,
-        r2: r2.to_bytes_be().1,
-    };
+    let algorithm = context.algorithm.as_ref().unwrap().read().await;
+    let mut statuses = Vec::new();
+    for context in [&unknown_user, &known_user] {
+        let challenge = context.challenge.as_ref().unwrap();
+        assert!(!challenge.auth_id.is_empty() && challenge.c > BigInt::from(0), "no plausible challenge");
+        let s = algorithm.solve_challenge(&algorithm.generate_random(), &challenge.k, &challenge.c);
+        statuses.push(context.server.verify_authentication(tonic::Request::new(AuthenticationAnswerRequest{
+            auth_id: challenge.auth_id.clone(),
+            s: s.to_bytes_be().1,
+        })).await.unwrap_err());
+    }
 
-    expect_unauthenticated(context.server.create_authentication_challenge(tonic::Request::new(challenge_request)).await);
+    assert_eq!(statuses[0].code(), tonic::Code::Unauthenticated);
+    assert_eq!(statuses[0].message(), statuses[1].message(), "messages differ");
+    assert_eq!(error_info(&statuses[0]), error_info(&statuses[1]), "error details differ");
 }
 
 #[tokio::test]
@@ -199,6 +412,36 @@ async fn get_challenge_with_empty_commitment_panics() {
     expect_invalid_argument(context.server.create_authentication_challenge(tonic::Request::new(challenge_request)).await);
 }
 
+#[tokio::test]
+async fn get_challenge_with_identity_commitment_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    let challenge_request = AuthenticationChallengeRequest{
+        user: context.user.unwrap().user.clone(),
+        r1: [0x01].to_vec(),
+        r2: [0x01].to_vec(),
+    };
+
+    expect_invalid_argument(context.server.create_authentication_challenge(tonic::Request::new(challenge_request)).await);
+}
+
+#[tokio::test]
+async fn challenges_are_scalars() {
+    let mut context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+    let q = context.algorithm.as_ref().unwrap().read().await.get_parameters().q.clone();
+
+    for _ in 0..20 {
+        context = context.with_challenge().await;
+        let c = &context.challenge.as_ref().unwrap().c;
+
+        assert!(c >= &BigInt::one() && c < &q, "challenge is not in [1, q)");
+    }
+}
+
 #[tokio::test]
 async fn solving_challenge_returns_session() {
     let context = TestContext::new()
@@ -206,7 +449,7 @@ async fn solving_challenge_returns_session() {
         .with_registered_user().await
         .with_challenge().await;
     let locked_algorithm = context.algorithm.unwrap();
-    let algorithm = locked_algorithm.read().unwrap();
+    let algorithm = locked_algorithm.read().await;
 
     let challenge = &context.challenge.unwrap();
     let s = algorithm.solve_challenge(&context.user.unwrap().x, &challenge.k, &challenge.c);
@@ -223,7 +466,7 @@ async fn solving_challenge_returns_session() {
 }
 
 #[tokio::test]
-async fn solving_challenge_of_first_challenge_panics() {
+async fn can_solve_first_of_two_challenges() {
     let context = TestContext::new()
         .with_algorithm().await
         .with_registered_user().await
@@ -233,9 +476,34 @@ async fn solving_challenge_of_first_challenge_panics() {
     let context = context.with_challenge().await;
 
     let locked_algorithm = context.algorithm.as_ref().unwrap();
-    let algorithm = locked_algorithm.read().unwrap();
+    let algorithm = locked_algorithm.read().await;
+
+    let s = algorithm.solve_challenge(&context.user.as_ref().unwrap().x, &first_challenge.k, &first_challenge.c);
+    let solution_request = AuthenticationAnswerRequest{
+        auth_id: first_challenge.auth_id.clone(),
+        s: s.to_bytes_be().1,
+    };
+    
+    assert!(context.server.verify_authentication(tonic::Request::new(solution_request)).await.is_ok(), "first challenge was replaced");
+    assert!(context.with_session().await.session.is_some(), "second challenge was consumed");
+}
+
+#[tokio::test]
+async fn solving_challenge_beyond_limit_panics() {
+    let mut config = test_config();
+    config.max_challenges_per_user = 1;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    
+    let first_challenge = context.challenge.clone().unwrap();
+    let context = context.with_challenge().await;
+
+    let locked_algorithm = context.algorithm.as_ref().unwrap();
+    let algorithm = locked_algorithm.read().await;
 
-    let s = algorithm.solve_challenge(&context.user.unwrap().x, &first_challenge.k, &first_challenge.c);
+    let s = algorithm.solve_challenge(&context.user.as_ref().unwrap().x, &first_challenge.k, &first_challenge.c);
     let solution_request = AuthenticationAnswerRequest{
         auth_id: first_challenge.auth_id.clone(),
         s: s.to_bytes_be().1,
@@ -260,6 +528,26 @@ async fn wrongly_solving_challenge_panics() {
     expect_unauthenticated(context.server.verify_authentication(tonic::Request::new(solution_request)).await);
 }
 
+#[tokio::test]
+async fn solving_challenge_with_s_out_of_range_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let locked_algorithm = context.algorithm.unwrap();
+    let algorithm = locked_algorithm.read().await;
+
+    // s + q passes the verification equation, but is not a valid scalar
+    let challenge = &context.challenge.unwrap();
+    let s = algorithm.solve_challenge(&context.user.unwrap().x, &challenge.k, &challenge.c) + &algorithm.get_parameters().q;
+    let solution_request = AuthenticationAnswerRequest{
+        auth_id: challenge.auth_id.clone(),
+        s: s.to_bytes_be().1,
+    };
+    
+    expect_invalid_argument(context.server.verify_authentication(tonic::Request::new(solution_request)).await);
+}
+
 #[tokio::test]
 async fn not_solving_challenge_panics() {
     let context = TestContext::new()
@@ -283,7 +571,7 @@ async fn solving_challenge_with_unkown_auth_id_panics() {
         .with_registered_user().await
         .with_challenge().await;
     let locked_algorithm = context.algorithm.unwrap();
-    let algorithm = locked_algorithm.read().unwrap();
+    let algorithm = locked_algorithm.read().await;
 
     let challenge = &context.challenge.unwrap();
     let s = algorithm.solve_challenge(&context.user.unwrap().x, &challenge.k, &challenge.c);
@@ -295,6 +583,747 @@ async fn solving_challenge_with_unkown_auth_id_panics() {
     expect_unauthenticated(context.server.verify_authentication(tonic::Request::new(solution_request)).await)
 }
 
+#[tokio::test]
+async fn non_interactive_proof_returns_session() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    let proof_request = context.non_interactive_proof(current_timestamp()).await;
+
+    let response = context.server.verify_non_interactive_authentication(tonic::Request::new(proof_request)).await;
+
+    assert!(response.is_ok());
+    let session = response.unwrap().into_inner();
+    assert!(!session.session_id.is_empty(), "no session id provided");
+}
+
+#[tokio::test]
+async fn non_interactive_proof_with_stale_timestamp_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    let proof_request = context.non_interactive_proof(current_timestamp() - 3600).await;
+
+    expect_unauthenticated(context.server.verify_non_interactive_authentication(tonic::Request::new(proof_request)).await);
+}
+
+#[tokio::test]
+async fn non_interactive_proof_with_changed_timestamp_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    let mut proof_request = context.non_interactive_proof(current_timestamp()).await;
+    proof_request.timestamp -= 1;
+
+    expect_unauthenticated(context.server.verify_non_interactive_authentication(tonic::Request::new(proof_request)).await);
+}
+
+#[tokio::test]
+async fn non_interactive_proof_used_twice_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    let proof_request = context.non_interactive_proof(current_timestamp()).await;
+
+    assert!(context.server.verify_non_interactive_authentication(tonic::Request::new(proof_request.clone())).await.is_ok());
+    let status = context.server.verify_non_interactive_authentication(tonic::Request::new(proof_request)).await.unwrap_err();
+
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    assert_eq!(error_reason(&status), Some(ErrorReason::ProofAlreadyUsed), "wrong error reason");
+}
+
+#[tokio::test]
+async fn non_interactive_proof_for_other_user_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+    let other_user = context.with_registered_user().await.user.unwrap();
+
+    let mut proof_request = context.non_interactive_proof(current_timestamp()).await;
+    proof_request.user = other_user.user;
+
+    expect_unauthenticated(context.server.verify_non_interactive_authentication(tonic::Request::new(proof_request)).await);
+}
+
+#[tokio::test]
+async fn non_interactive_proof_with_non_existing_user_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    let mut proof_request = context.non_interactive_proof(current_timestamp()).await;
+    proof_request.user = Uuid::new_v4().to_string();
+
+    expect_unauthenticated(context.server.verify_non_interactive_authentication(tonic::Request::new(proof_request)).await);
+}
+
+#[tokio::test]
+async fn non_interactive_proof_without_solution_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    let mut proof_request = context.non_interactive_proof(current_timestamp()).await;
+    proof_request.s = [].to_vec();
+
+    expect_invalid_argument(context.server.verify_non_interactive_authentication(tonic::Request::new(proof_request)).await);
+}
+
+#[tokio::test]
+async fn session_can_be_validated() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{
+        session_id: context.session.unwrap()
+    })).await;
+
+    assert!(response.is_ok());
+    assert_eq!(response.unwrap().into_inner().user, context.user.unwrap().user, "session belongs to wrong user");
+}
+
+#[tokio::test]
+async fn validate_unknown_session_panics() {
+    let context = TestContext::new();
+
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{
+        session_id: Uuid::new_v4().to_string()
+    })).await;
+
+    expect_unauthenticated(response);
+}
+
+#[tokio::test]
+async fn validate_empty_session_panics() {
+    let context = TestContext::new();
+
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{
+        session_id: String::from("")
+    })).await;
+
+    expect_invalid_argument(response);
+}
+
+#[tokio::test]
+async fn refreshing_session_replaces_it() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let old_session = context.session.unwrap();
+
+    let response = context.server.refresh_session(tonic::Request::new(SessionRequest{
+        session_id: old_session.clone()
+    })).await;
+
+    assert!(response.is_ok());
+    let new_session = response.unwrap().into_inner().session_id;
+    assert_ne!(new_session, old_session, "session was not replaced");
+    assert!(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: new_session })).await.is_ok());
+    expect_unauthenticated(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: old_session })).await);
+}
+
+#[tokio::test]
+async fn logout_ends_session() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let session = context.session.unwrap();
+
+    let response = context.server.logout(tonic::Request::new(SessionRequest{
+        session_id: session.clone()
+    })).await;
+
+    assert!(response.is_ok());
+    expect_unauthenticated(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: session.clone() })).await);
+    expect_unauthenticated(context.server.logout(tonic::Request::new(SessionRequest{ session_id: session })).await);
+}
+
+#[tokio::test]
+async fn sessions_of_several_logins_are_all_valid() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let first_session = context.session.clone().unwrap();
+    let context = context.with_challenge().await.with_session().await;
+
+    assert!(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: first_session })).await.is_ok(), "first session was replaced");
+    assert!(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: context.session.unwrap() })).await.is_ok());
+}
+
+#[tokio::test]
+async fn sessions_beyond_limit_are_ended() {
+    let mut config = test_config();
+    config.max_sessions_per_user = 2;
+    let mut context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await;
+    let mut sessions = Vec::new();
+    for _ in 0..3 {
+        context = context.with_challenge().await.with_session().await;
+        sessions.push(context.session.clone().unwrap());
+    }
+
+    let mut valid = 0;
+    for session_id in &sessions {
+        if context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: session_id.clone() })).await.is_ok() {
+            valid += 1;
+        }
+    }
+
+    assert_eq!(valid, 2, "wrong number of active sessions");
+    assert!(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: context.session.unwrap() })).await.is_ok(), "newest session was ended");
+}
+
+#[tokio::test]
+async fn can_list_sessions() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let first_session = context.session.clone().unwrap();
+    let context = context.with_challenge().await.with_session().await;
+    let second_session = context.session.clone().unwrap();
+
+    let response = context.server.list_sessions(tonic::Request::new(SessionRequest{ session_id: second_session.clone() })).await;
+
+    assert!(response.is_ok());
+    let sessions = response.unwrap().into_inner().sessions;
+    assert_eq!(sessions.len(), 2, "wrong number of sessions");
+    assert_eq!(sessions.iter().filter(|session| session.current).count(), 1, "current session not marked");
+    assert!(sessions.iter().all(|session| session.expires_at > current_timestamp()), "expired session listed");
+    assert!(sessions.iter().all(|session| session.session_ref != first_session && session.session_ref != second_session), "session ids are exposed");
+}
+
+#[tokio::test]
+async fn can_revoke_other_session() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let first_session = context.session.clone().unwrap();
+    let context = context.with_challenge().await.with_session().await;
+    let second_session = context.session.clone().unwrap();
+    let sessions = context.server.list_sessions(tonic::Request::new(SessionRequest{ session_id: second_session.clone() })).await.unwrap().into_inner().sessions;
+    let other = sessions.iter().find(|session| !session.current).unwrap();
+
+    let response = context.server.revoke_session(tonic::Request::new(RevokeSessionRequest{
+        session_id: second_session.clone(),
+        session_ref: other.session_ref.clone()
+    })).await;
+
+    assert!(response.is_ok());
+    expect_unauthenticated(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: first_session })).await);
+    assert!(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: second_session })).await.is_ok(), "wrong session revoked");
+}
+
+#[tokio::test]
+async fn revoking_session_of_other_user_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let victim_session = context.session.clone().unwrap();
+    let victim_ref = context.server.list_sessions(tonic::Request::new(SessionRequest{ session_id: victim_session.clone() })).await.unwrap().into_inner().sessions[0].session_ref.clone();
+    let context = context.with_registered_user().await.with_challenge().await.with_session().await;
+
+    let status = context.server.revoke_session(tonic::Request::new(RevokeSessionRequest{
+        session_id: context.session.unwrap(),
+        session_ref: victim_ref
+    })).await.unwrap_err();
+
+    assert_eq!(status.code(), tonic::Code::NotFound);
+    assert_eq!(error_reason(&status), Some(ErrorReason::SessionRefNotFound), "wrong error reason");
+    assert!(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: victim_session })).await.is_ok(), "session of other user was revoked");
+}
+
+#[tokio::test]
+async fn changing_secret_replaces_it() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let old_user = context.user.clone();
+
+    let context = context.with_changed_secret(NEW_PASSWORD).await
+        .with_challenge().await
+        .with_session().await;
+
+    assert!(context.session.is_some(), "no session provided");
+    let context = TestContext {
+        user: old_user,
+        ..context
+    }.with_challenge().await;
+    let challenge = context.challenge.unwrap();
+    let s = context.algorithm.unwrap().read().await.solve_challenge(&context.user.unwrap().x, &challenge.k, &challenge.c);
+    expect_unauthenticated(context.server.verify_authentication(tonic::Request::new(AuthenticationAnswerRequest{
+        auth_id: challenge.auth_id,
+        s: s.to_bytes_be().1
+    })).await);
+}
+
+#[tokio::test]
+async fn changing_secret_ends_existing_sessions() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let old_session = context.session.clone().unwrap();
+
+    let context = context.with_challenge().await.with_changed_secret(NEW_PASSWORD).await;
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: old_session })).await;
+
+    assert_eq!(error_reason(&response.unwrap_err()), Some(ErrorReason::SessionNotFound), "session survived the secret change");
+}
+
+#[tokio::test]
+async fn changing_secret_with_wrong_proof_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let (_, mut change_request) = context.change_secret_request(NEW_PASSWORD).await;
+    change_request.s = (BigInt::from_bytes_be(num_bigint::Sign::Plus, &change_request.s) + BigInt::one()).to_bytes_be().1;
+
+    expect_unauthenticated(context.server.change_secret(tonic::Request::new(change_request)).await);
+
+    let context = context.with_challenge().await.with_session().await;
+    assert!(context.session.is_some(), "old secret was replaced");
+}
+
+#[tokio::test]
+async fn changing_secret_to_zero_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let (_, mut change_request) = context.change_secret_request(NEW_PASSWORD).await;
+    change_request.y1 = vec![0];
+
+    let status = context.server.change_secret(tonic::Request::new(change_request)).await.unwrap_err();
+
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert_eq!(error_info(&status).unwrap().metadata.get(FIELD_METADATA_KEY).map(String::as_str), Some("y1"), "wrong field");
+}
+
+#[tokio::test]
+async fn changing_secret_to_weak_parameters_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    for secret_parameters in weak_secret_parameters() {
+        let context = context.with_challenge().await;
+        let (_, mut change_request) = context.change_secret_request(NEW_PASSWORD).await;
+        change_request.secret_parameters = Some(to_grpc_secret_parameters(&secret_parameters));
+
+        let status = context.server.change_secret(tonic::Request::new(change_request)).await.unwrap_err();
+
+        assert_eq!(error_reason(&status), Some(ErrorReason::InvalidSecretParameters), "wrong error reason for {:?}", secret_parameters);
+    }
+
+    let context = context.with_challenge().await.with_session().await;
+    assert!(context.session.is_some(), "old secret was replaced");
+}
+
+#[tokio::test]
+async fn changing_secret_to_expensive_parameters_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    for secret_parameters in expensive_secret_parameters() {
+        let context = context.with_challenge().await;
+        let (_, mut change_request) = context.change_secret_request(NEW_PASSWORD).await;
+        change_request.secret_parameters = Some(to_grpc_secret_parameters(&secret_parameters));
+
+        let status = context.server.change_secret(tonic::Request::new(change_request)).await.unwrap_err();
+
+        assert_eq!(error_reason(&status), Some(ErrorReason::InvalidSecretParameters), "wrong error reason for {:?}", secret_parameters);
+    }
+}
+
+#[tokio::test]
+async fn changing_secret_twice_with_same_proof_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let (_, change_request) = context.change_secret_request(NEW_PASSWORD).await;
+
+    assert!(context.server.change_secret(tonic::Request::new(change_request.clone())).await.is_ok());
+    let status = context.server.change_secret(tonic::Request::new(change_request)).await.unwrap_err();
+
+    assert_eq!(error_reason(&status), Some(ErrorReason::ChallengeNotFound), "wrong error reason");
+}
+
+#[tokio::test]
+async fn unregistering_user_deletes_it_with_its_sessions() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await
+        .with_challenge().await;
+    let unregister_request = context.unregister_request().await;
+
+    let response = context.server.unregister(tonic::Request::new(unregister_request)).await;
+
+    assert!(response.is_ok());
+    expect_unauthenticated(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: context.session.clone().unwrap() })).await);
+    let context = context.with_challenge().await;
+    let challenge = context.challenge.unwrap();
+    let s = context.algorithm.unwrap().read().await.solve_challenge(&context.user.unwrap().x, &challenge.k, &challenge.c);
+    expect_unauthenticated(context.server.verify_authentication(tonic::Request::new(AuthenticationAnswerRequest{
+        auth_id: challenge.auth_id,
+        s: s.to_bytes_be().1,
+    })).await);
+}
+
+#[tokio::test]
+async fn unregistering_user_with_wrong_proof_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let mut unregister_request = context.unregister_request().await;
+    unregister_request.s = (BigInt::from_bytes_be(num_bigint::Sign::Plus, &unregister_request.s) + BigInt::one()).to_bytes_be().1;
+
+    expect_unauthenticated(context.server.unregister(tonic::Request::new(unregister_request)).await);
+
+    let context = context.with_challenge().await.with_session().await;
+    assert!(context.session.is_some(), "user was deleted");
+}
+
+#[tokio::test]
+async fn unregistering_other_user_than_the_one_of_the_challenge_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+    let other_user = context.with_registered_user().await.user.unwrap().user;
+    let context = context.with_challenge().await;
+    let mut unregister_request = context.unregister_request().await;
+    unregister_request.user = other_user.clone();
+
+    expect_unauthenticated(context.server.unregister(tonic::Request::new(unregister_request)).await);
+
+    assert!(context.server.get_secret_parameters(tonic::Request::new(SecretParametersRequest{ user: other_user })).await.is_ok(), "other user was deleted");
+    let context = context.with_challenge().await.with_session().await;
+    assert!(context.session.is_some(), "user was deleted");
+}
+
+#[tokio::test]
+async fn admin_can_remove_user() {
+    let mut config = test_config();
+    config.admin_token = Some(String::from(ADMIN_TOKEN));
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let user = context.user.unwrap().user;
+
+    let response = context.server.unregister(admin_request(&user, ADMIN_TOKEN)).await;
+
+    assert!(response.is_ok());
+    expect_unauthenticated(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: context.session.unwrap() })).await);
+    let status = context.server.unregister(admin_request(&user, ADMIN_TOKEN)).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+    assert_eq!(error_reason(&status), Some(ErrorReason::UserNotFound), "wrong error reason");
+}
+
+#[tokio::test]
+async fn removing_user_with_wrong_admin_token_panics() {
+    let mut config = test_config();
+    config.admin_token = Some(String::from(ADMIN_TOKEN));
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await;
+    let user = context.user.unwrap().user;
+
+    let status = context.server.unregister(admin_request(&user, "wrong token")).await.unwrap_err();
+
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    assert_eq!(error_reason(&status), Some(ErrorReason::AdminAccessDenied), "wrong error reason");
+    assert!(context.server.get_secret_parameters(tonic::Request::new(SecretParametersRequest{ user })).await.is_ok(), "user was deleted");
+}
+
+#[tokio::test]
+async fn removing_user_without_configured_admin_token_panics() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+
+    let status = context.server.unregister(admin_request(&context.user.unwrap().user, "")).await.unwrap_err();
+
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn solving_expired_challenge_panics() {
+    let mut config = test_config();
+    config.challenge_ttl = 0;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let locked_algorithm = context.algorithm.unwrap();
+    let algorithm = locked_algorithm.read().await;
+
+    let challenge = &context.challenge.unwrap();
+    let s = algorithm.solve_challenge(&context.user.unwrap().x, &challenge.k, &challenge.c);
+    let solution_request = AuthenticationAnswerRequest{
+        auth_id: challenge.auth_id.clone(),
+        s: s.to_bytes_be().1,
+    };
+
+    expect_unauthenticated(context.server.verify_authentication(tonic::Request::new(solution_request)).await);
+}
+
+#[tokio::test]
+async fn expired_session_panics() {
+    let mut config = test_config();
+    config.session_ttl = 0;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let session = context.session.unwrap();
+
+    expect_unauthenticated(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: session.clone() })).await);
+    expect_unauthenticated(context.server.refresh_session(tonic::Request::new(SessionRequest{ session_id: session })).await);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_users_can_authenticate() {
+    let context = TestContext::new()
+        .with_algorithm().await;
+
+    let logins: Vec<_> = (0..8).map(|_| {
+        let context = TestContext {
+            server: context.server.clone(),
+            algorithm: context.algorithm.clone(),
+            user: None,
+            challenge: None,
+            session: None
+        };
+        tokio::spawn(async move {
+            context.with_registered_user().await
+                .with_challenge().await
+                .with_session().await
+                .session
+        })
+    }).collect();
+
+    for login in logins {
+        assert!(login.await.unwrap().is_some(), "no session provided");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_refreshes_create_one_session() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let session = context.session.unwrap();
+
+    let refreshes: Vec<_> = (0..8).map(|_| {
+        let server = context.server.clone();
+        let session_id = session.clone();
+        tokio::spawn(async move {
+            server.refresh_session(tonic::Request::new(SessionRequest{ session_id })).await
+        })
+    }).collect();
+
+    let mut refreshed = 0;
+    for refresh in refreshes {
+        if refresh.await.unwrap().is_ok() {
+            refreshed += 1;
+        }
+    }
+    assert_eq!(refreshed, 1, "session was refreshed more than once");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_registrations_create_one_user() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+    let user = context.user.unwrap();
+    let register_request = RegisterRequest{
+        user: Uuid::new_v4().to_string(),
+        y1: user.y1.to_bytes_be().1,
+        y2: user.y2.to_bytes_be().1,
+        secret_parameters: Some(to_grpc_secret_parameters(&user.secret_parameters)),
+    };
+
+    let registrations: Vec<_> = (0..8).map(|_| {
+        let server = context.server.clone();
+        let register_request = register_request.clone();
+        tokio::spawn(async move {
+            server.register(tonic::Request::new(register_request)).await
+        })
+    }).collect();
+
+    let mut registered = 0;
+    for registration in registrations {
+        match registration.await.unwrap() {
+            Ok(_) => registered += 1,
+            Err(status) => assert_eq!(status.code(), tonic::Code::AlreadyExists, "expected already exists error")
+        }
+    }
+    assert_eq!(registered, 1, "user was registered more than once");
+}
+
+#[tokio::test]
+async fn can_get_ristretto255_parameters() {
+    let mut config = test_config();
+    config.group = GroupType::Ristretto255;
+    let context = TestContext::with_config(config);
+
+    let paramters = context.server.get_authentication_parameters(tonic::Request::new(())).await;
+
+    assert!(paramters.is_ok());
+    let paramters = paramters.unwrap().into_inner();
+    assert_eq!(paramters.group, "ristretto255", "wrong group advertised");
+    assert_eq!(paramters.g.len(), 32, "generator is not a compressed point");
+    assert_eq!(paramters.h.len(), 32, "generator is not a compressed point");
+}
+
+#[tokio::test]
+async fn solving_ristretto255_challenge_returns_session() {
+    let mut config = test_config();
+    config.group = GroupType::Ristretto255;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let locked_algorithm = context.algorithm.unwrap();
+    let algorithm = locked_algorithm.read().await;
+
+    let challenge = &context.challenge.unwrap();
+    let s = algorithm.solve_challenge(&context.user.unwrap().x, &challenge.k, &challenge.c);
+    let solution_request = AuthenticationAnswerRequest{
+        auth_id: challenge.auth_id.clone(),
+        s: s.to_bytes_be().1,
+    };
+    
+    let response = context.server.verify_authentication(tonic::Request::new(solution_request)).await;
+
+    assert!(response.is_ok());
+    let session = response.unwrap().into_inner();
+    assert!(!session.session_id.is_empty(), "no session id provided");
+}
+
+#[tokio::test]
+async fn wrongly_solving_ristretto255_challenge_panics() {
+    let mut config = test_config();
+    config.group = GroupType::Ristretto255;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+
+    let challenge = &context.challenge.unwrap();
+    let solution_request = AuthenticationAnswerRequest{
+        auth_id: challenge.auth_id.clone(),
+        s: [0xAA].to_vec(),
+    };
+    
+    expect_unauthenticated(context.server.verify_authentication(tonic::Request::new(solution_request)).await);
+}
+
+#[tokio::test]
+async fn errors_carry_reason_and_field() {
+    let context = TestContext::new()
+        .with_algorithm().await;
+
+    let response = context.server.get_secret_parameters(tonic::Request::new(SecretParametersRequest{
+        user: String::from("")
+    })).await;
+
+    let status = response.unwrap_err();
+    let info = error_info(&status).expect("no error info provided");
+    assert_eq!(info.domain, ERROR_DOMAIN, "wrong error domain");
+    assert_eq!(error_reason(&status), Some(ErrorReason::EmptyField), "wrong error reason");
+    assert_eq!(info.metadata.get(FIELD_METADATA_KEY).map(String::as_str), Some("user"), "wrong field");
+}
+
+#[tokio::test]
+async fn unknown_user_and_wrong_proof_are_indistinguishable() {
+    let context = TestContext::new()
+        .with_algorithm().await
+        .with_registered_user().await;
+    let unknown_user = TestContext {
+        user: Some(TestUser {
+            user: Uuid::new_v4().to_string(),
+            ..context.user.clone().unwrap()
+        }),
+        server: context.server.clone(),
+        algorithm: context.algorithm.clone(),
+        challenge: None,
+        session: None
+    };
+    let mut wrong_proof = context.non_interactive_proof(current_timestamp()).await;
+    wrong_proof.s = [0xAA].to_vec();
+
+    let unknown_user = context.server.verify_non_interactive_authentication(tonic::Request::new(unknown_user.non_interactive_proof(current_timestamp()).await)).await.unwrap_err();
+    let wrong_proof = context.server.verify_non_interactive_authentication(tonic::Request::new(wrong_proof)).await.unwrap_err();
+
+    assert_eq!(unknown_user.code(), wrong_proof.code(), "codes differ");
+    assert_eq!(unknown_user.message(), wrong_proof.message(), "messages differ");
+    assert_eq!(error_info(&unknown_user), error_info(&wrong_proof), "error details differ");
+    assert_eq!(error_reason(&unknown_user), Some(ErrorReason::AuthenticationFailed), "wrong error reason");
+}
+
+#[tokio::test]
+async fn expired_challenge_has_own_reason() {
+    let mut config = test_config();
+    config.challenge_ttl = 0;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+
+    let solution_request = AuthenticationAnswerRequest{
+        auth_id: context.challenge.unwrap().auth_id,
+        s: [0xAA].to_vec(),
+    };
+    let status = context.server.verify_authentication(tonic::Request::new(solution_request)).await.unwrap_err();
+
+    assert_eq!(error_reason(&status), Some(ErrorReason::ChallengeExpired), "wrong error reason");
+}
+
+const ADMIN_TOKEN: &str = "My Admin Token Which Is Long Enough";
+
+fn admin_request(user: &str, admin_token: &str) -> tonic::Request<UnregisterRequest> {
+    let mut request = tonic::Request::new(UnregisterRequest{
+        user: user.to_string(),
+        ..Default::default()
+    });
+    request.metadata_mut().insert(AUTHORIZATION_METADATA_KEY, format!("Bearer {}", admin_token).parse().unwrap());
+    request
+}
+
 fn expect_unauthenticated<T: fmt::Debug>(result: Result<T, tonic::Status>) {
     assert!(result.is_err(), "expected error");
     assert_eq!(result.unwrap_err().code(), tonic::Code::Unauthenticated, "expected unauthenticated error");
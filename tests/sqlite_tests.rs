@@ -0,0 +1,282 @@
+#[allow(dead_code)]
+mod server;
+
+use auth_lib::cp_grpc::{AuthenticationAnswerRequest, SessionRequest, SecretParametersRequest};
+use auth_lib::chaum_pedersen::algorithm::ChaumPedersen;
+use auth_lib::Config;
+use auth_lib::grpc::error_details::{error_reason, ErrorReason};
+use server::*;
+use uuid::Uuid;
+use tempfile::TempDir;
+
+fn sqlite_config(directory: &TempDir) -> Config {
+    let mut config = test_config();
+    config.database_url = Some(format!("sqlite://{}", directory.path().join("auth.db").display()));
+    config.redis_url = None;
+    config
+}
+
+#[tokio::test]
+async fn can_authenticate_with_sqlite() {
+    let directory = tempfile::tempdir().unwrap();
+    let context = TestContext::with_config(sqlite_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{
+        session_id: context.session.unwrap()
+    })).await;
+
+    assert!(response.is_ok());
+    assert_eq!(response.unwrap().into_inner().user, context.user.unwrap().user, "session belongs to wrong user");
+}
+
+#[tokio::test]
+async fn users_survive_restart() {
+    let directory = tempfile::tempdir().unwrap();
+    let context = TestContext::with_config(sqlite_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await;
+    let restarted = TestContext::with_config(sqlite_config(&directory));
+    let context = TestContext {
+        server: restarted.server,
+        ..context
+    };
+
+    let secret_parameters = context.server.get_secret_parameters(tonic::Request::new(SecretParametersRequest{
+        user: context.user.as_ref().unwrap().user.clone()
+    })).await;
+    let context = context.with_challenge().await.with_session().await;
+
+    assert_eq!(secret_parameters.unwrap().into_inner(), to_grpc_secret_parameters(&context.user.unwrap().secret_parameters));
+    assert!(context.session.is_some());
+}
+
+#[tokio::test]
+async fn logout_removes_session_from_sqlite() {
+    let directory = tempfile::tempdir().unwrap();
+    let context = TestContext::with_config(sqlite_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let session = context.session.unwrap();
+
+    assert!(context.server.logout(tonic::Request::new(SessionRequest{ session_id: session.clone() })).await.is_ok());
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: session })).await;
+
+    assert_eq!(response.unwrap_err().code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn used_proof_is_rejected_after_restart() {
+    let directory = tempfile::tempdir().unwrap();
+    let context = TestContext::with_config(sqlite_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await;
+    let proof = context.non_interactive_proof(current_timestamp()).await;
+
+    let accepted = context.server.verify_non_interactive_authentication(tonic::Request::new(proof.clone())).await;
+    let restarted = TestContext::with_config(sqlite_config(&directory));
+    let response = restarted.server.verify_non_interactive_authentication(tonic::Request::new(proof)).await;
+
+    assert!(accepted.is_ok());
+    assert_eq!(error_reason(&response.unwrap_err()), Some(ErrorReason::ProofAlreadyUsed), "wrong error reason");
+}
+
+#[tokio::test]
+async fn solving_challenge_beyond_limit_with_sqlite_panics() {
+    let directory = tempfile::tempdir().unwrap();
+    let mut config = sqlite_config(&directory);
+    config.max_challenges_per_user = 1;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await;
+    let first_challenge = context.challenge.clone().unwrap();
+    let context = context.with_challenge().await;
+
+    let algorithm = context.algorithm.as_ref().unwrap().read().await;
+    let s = algorithm.solve_challenge(&context.user.as_ref().unwrap().x, &first_challenge.k, &first_challenge.c);
+    let response = context.server.verify_authentication(tonic::Request::new(AuthenticationAnswerRequest{
+        auth_id: first_challenge.auth_id.clone(),
+        s: s.to_bytes_be().1,
+    })).await;
+
+    assert_eq!(response.unwrap_err().code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn storage_failure_is_unavailable() {
+    let directory = tempfile::tempdir().unwrap();
+    let context = TestContext::with_config(sqlite_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let connection = rusqlite::Connection::open(directory.path().join("auth.db")).unwrap();
+    connection.execute_batch("DROP TABLE sessions").unwrap();
+
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{
+        session_id: context.session.unwrap()
+    })).await;
+
+    let status = response.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unavailable);
+    assert_eq!(error_reason(&status), Some(ErrorReason::StorageUnavailable));
+}
+
+#[tokio::test]
+async fn unsupported_database_is_rejected() {
+    let mut config = test_config();
+    config.database_url = Some(String::from("mysql://localhost/auth"));
+
+    assert!(auth_lib::bootstrap_server(Some(config)).is_err());
+}
+
+#[tokio::test]
+async fn changed_secret_survives_restart() {
+    let directory = tempfile::tempdir().unwrap();
+    let context = TestContext::with_config(sqlite_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_changed_secret(NEW_PASSWORD).await;
+    let restarted = TestContext::with_config(sqlite_config(&directory));
+
+    let context = TestContext {
+        server: restarted.server,
+        ..context
+    }.with_challenge().await.with_session().await;
+
+    assert!(context.session.is_some(), "no session provided");
+}
+
+#[tokio::test]
+async fn unregistered_user_is_removed_from_sqlite() {
+    let directory = tempfile::tempdir().unwrap();
+    let context = TestContext::with_config(sqlite_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await
+        .with_challenge().await;
+    let unregister_request = context.unregister_request().await;
+
+    assert!(context.server.unregister(tonic::Request::new(unregister_request)).await.is_ok());
+    let restarted = TestContext::with_config(sqlite_config(&directory));
+    let response = restarted.server.validate_session(tonic::Request::new(SessionRequest{ session_id: context.session.unwrap() })).await;
+
+    assert_eq!(response.unwrap_err().code(), tonic::Code::Unauthenticated);
+    let connection = rusqlite::Connection::open(directory.path().join("auth.db")).unwrap();
+    let users: i64 = connection.query_row("SELECT COUNT(*) FROM users WHERE id = ?1", [context.user.unwrap().user], |row| row.get(0)).unwrap();
+    assert_eq!(users, 0, "user was not deleted");
+}
+
+#[tokio::test]
+async fn lockout_survives_restart() {
+    let directory = tempfile::tempdir().unwrap();
+    let config = || {
+        let mut config = sqlite_config(&directory);
+        config.user_failure_limit = 1;
+        config.base_lockout = 60;
+        config
+    };
+    let context = TestContext::with_config(config())
+        .with_algorithm().await
+        .with_registered_user().await;
+    let mut proof = context.non_interactive_proof(current_timestamp()).await;
+    proof.s = vec![1];
+
+    let failed = context.server.verify_non_interactive_authentication(tonic::Request::new(proof.clone())).await;
+    let restarted = TestContext::with_config(config());
+    let response = restarted.server.verify_non_interactive_authentication(tonic::Request::new(proof)).await;
+
+    assert_eq!(failed.unwrap_err().code(), tonic::Code::Unauthenticated);
+    assert_eq!(error_reason(&response.unwrap_err()), Some(ErrorReason::TooManyAttempts), "wrong error reason");
+}
+
+#[tokio::test]
+async fn unknown_user_fails_at_verification_with_sqlite() {
+    let directory = tempfile::tempdir().unwrap();
+    let context = TestContext::with_config(sqlite_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await;
+    let context = TestContext {
+        user: Some(TestUser {
+            user: Uuid::new_v4().to_string(),
+            ..context.user.clone().unwrap()
+        }),
+        ..context
+    }.with_challenge().await;
+
+    let challenge = context.challenge.as_ref().unwrap();
+    let s = context.algorithm.as_ref().unwrap().read().await.solve_challenge(&context.user.as_ref().unwrap().x, &challenge.k, &challenge.c);
+    let status = context.server.verify_authentication(tonic::Request::new(AuthenticationAnswerRequest{
+        auth_id: challenge.auth_id.clone(),
+        s: s.to_bytes_be().1,
+    })).await.unwrap_err();
+
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    assert_eq!(error_reason(&status), Some(ErrorReason::AuthenticationFailed), "wrong error reason");
+}
+
+// A database created before the later migrations existed, with only the first one applied
+fn sqlite_database_at_version_1(directory: &TempDir) {
+    let connection = rusqlite::Connection::open(directory.path().join("auth.db")).unwrap();
+    connection.execute_batch(include_str!("../migrations/sqlite/0001_create_tables.sql")).unwrap();
+    connection.pragma_update(None, "user_version", 1).unwrap();
+}
+
+#[tokio::test]
+async fn database_at_version_1_is_upgraded() {
+    let directory = tempfile::tempdir().unwrap();
+    sqlite_database_at_version_1(&directory);
+    let mut config = sqlite_config(&directory);
+    config.user_failure_limit = 1;
+    config.base_lockout = 60;
+    let context = TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await;
+    let mut proof = context.non_interactive_proof(current_timestamp()).await;
+    proof.s = vec![1];
+
+    let failed = context.server.verify_non_interactive_authentication(tonic::Request::new(proof.clone())).await;
+    let response = context.server.verify_non_interactive_authentication(tonic::Request::new(proof)).await;
+
+    assert!(context.session.is_some(), "no session provided");
+    assert_eq!(failed.unwrap_err().code(), tonic::Code::Unauthenticated);
+    assert_eq!(error_reason(&response.unwrap_err()), Some(ErrorReason::TooManyAttempts), "wrong error reason");
+    let connection = rusqlite::Connection::open(directory.path().join("auth.db")).unwrap();
+    let version: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+    assert_eq!(version, 5, "not all migrations were applied");
+}
+
+#[tokio::test]
+async fn unknown_user_gets_challenge_after_upgrade() {
+    let directory = tempfile::tempdir().unwrap();
+    sqlite_database_at_version_1(&directory);
+    let context = TestContext::with_config(sqlite_config(&directory))
+        .with_algorithm().await
+        .with_registered_user().await;
+    let context = TestContext {
+        user: Some(TestUser {
+            user: Uuid::new_v4().to_string(),
+            ..context.user.clone().unwrap()
+        }),
+        ..context
+    }.with_challenge().await;
+
+    let challenge = context.challenge.as_ref().unwrap();
+    let s = context.algorithm.as_ref().unwrap().read().await.solve_challenge(&context.user.as_ref().unwrap().x, &challenge.k, &challenge.c);
+    let status = context.server.verify_authentication(tonic::Request::new(AuthenticationAnswerRequest{
+        auth_id: challenge.auth_id.clone(),
+        s: s.to_bytes_be().1,
+    })).await.unwrap_err();
+
+    assert_eq!(error_reason(&status), Some(ErrorReason::AuthenticationFailed), "wrong error reason");
+}
\ No newline at end of file
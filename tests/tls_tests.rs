@@ -0,0 +1,155 @@
+use std::fs;
+use std::net::SocketAddr;
+
+use auth_lib::cp_grpc::auth_server::AuthServer;
+use auth_lib::grpc::chaum_pedersen_client::AuthClient;
+use auth_lib::{bootstrap_client, bootstrap_server, server_tls_config, Config};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa};
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+use uuid::Uuid;
+
+const DOMAIN: &str = "localhost";
+
+// Writes a CA which signs a server and a client certificate and an unrelated CA into the directory
+fn generate_certificates() -> TempDir {
+    let directory = tempfile::tempdir().unwrap();
+    let ca = certificate_authority("Test CA");
+    let other_ca = certificate_authority("Other CA");
+    let server = Certificate::from_params(CertificateParams::new(vec![DOMAIN.to_string()])).unwrap();
+    let client = Certificate::from_params(CertificateParams::new(vec![String::from("client")])).unwrap();
+
+    let write = |name: &str, pem: String| fs::write(directory.path().join(name), pem).unwrap();
+    write("ca.pem", ca.serialize_pem().unwrap());
+    write("other_ca.pem", other_ca.serialize_pem().unwrap());
+    write("server.pem", server.serialize_pem_with_signer(&ca).unwrap());
+    write("server.key", server.serialize_private_key_pem());
+    write("client.pem", client.serialize_pem_with_signer(&ca).unwrap());
+    write("client.key", client.serialize_private_key_pem());
+    directory
+}
+
+fn certificate_authority(name: &str) -> Certificate {
+    let mut params = CertificateParams::new(vec![]);
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.distinguished_name.push(DnType::CommonName, name);
+    Certificate::from_params(params).unwrap()
+}
+
+fn server_config(certificates: &TempDir, mutual: bool) -> Config {
+    let mut config = Config::build().unwrap();
+    config.fixed_parameters = true;
+    config.tls_cert = Some(certificates.path().join("server.pem"));
+    config.tls_key = Some(certificates.path().join("server.key"));
+    if mutual {
+        config.tls_client_ca = Some(certificates.path().join("ca.pem"));
+    }
+    config
+}
+
+fn client_config(address: SocketAddr, ca: Option<std::path::PathBuf>) -> Config {
+    let mut config = Config::build().unwrap();
+    config.host = address.ip().to_string();
+    config.port = address.port();
+    config.tls_ca = ca;
+    config.tls_domain = Some(DOMAIN.to_string());
+    config
+}
+
+// Serves the configured server on a free port of the loopback interface
+async fn serve(config: Config) -> SocketAddr {
+    let tls = server_tls_config(&config).unwrap();
+    let server = bootstrap_server(Some(config)).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let mut builder = Server::builder();
+    if let Some(tls) = tls {
+        builder = builder.tls_config(tls).unwrap();
+    }
+    tokio::spawn(builder
+        .add_service(AuthServer::new(server))
+        .serve_with_incoming(TcpListenerStream::new(listener)));
+    address
+}
+
+async fn connect(config: Config) -> Result<Box<dyn AuthClient>, Box<dyn std::error::Error>> {
+    bootstrap_client::<Box<dyn AuthClient>, String>(config.server_url(), Some(config)).await
+}
+
+#[tokio::test]
+async fn client_with_pinned_ca_can_authenticate() {
+    let certificates = generate_certificates();
+    let address = serve(server_config(&certificates, false)).await;
+    let user = Uuid::new_v4().to_string();
+
+    let client = connect(client_config(address, Some(certificates.path().join("ca.pem")))).await.unwrap();
+    client.register_user(&user, "My Super Secret Password").await.unwrap();
+    let session = client.authenticate_user_non_interactive(&user, "My Super Secret Password").await.unwrap();
+
+    assert_eq!(client.validate_session(&session).await.unwrap(), user, "session belongs to wrong user");
+}
+
+#[tokio::test]
+async fn client_with_other_ca_is_rejected() {
+    let certificates = generate_certificates();
+    let address = serve(server_config(&certificates, false)).await;
+
+    let client = connect(client_config(address, Some(certificates.path().join("other_ca.pem")))).await;
+
+    assert!(client.is_err(), "server certificate was accepted");
+}
+
+#[tokio::test]
+async fn plaintext_client_is_rejected() {
+    let certificates = generate_certificates();
+    let address = serve(server_config(&certificates, false)).await;
+
+    let client = connect(client_config(address, None)).await;
+
+    assert!(client.is_err(), "plaintext connection was accepted");
+}
+
+#[tokio::test]
+async fn mutual_tls_accepts_client_certificate() {
+    let certificates = generate_certificates();
+    let address = serve(server_config(&certificates, true)).await;
+    let mut config = client_config(address, Some(certificates.path().join("ca.pem")));
+    config.tls_cert = Some(certificates.path().join("client.pem"));
+    config.tls_key = Some(certificates.path().join("client.key"));
+
+    let client = connect(config).await;
+
+    assert!(client.is_ok(), "client certificate was rejected");
+}
+
+#[tokio::test]
+async fn mutual_tls_rejects_client_without_certificate() {
+    let certificates = generate_certificates();
+    let address = serve(server_config(&certificates, true)).await;
+
+    let client = connect(client_config(address, Some(certificates.path().join("ca.pem")))).await;
+
+    assert!(client.is_err(), "client without certificate was accepted");
+}
+
+#[test]
+fn client_ca_without_server_certificate_is_rejected() {
+    let certificates = generate_certificates();
+    let mut config = server_config(&certificates, true);
+    config.tls_cert = None;
+    config.tls_key = None;
+
+    assert!(server_tls_config(&config).is_err());
+}
+
+#[test]
+fn certificate_without_key_is_rejected() {
+    let certificates = generate_certificates();
+    let mut config = server_config(&certificates, false);
+    config.tls_key = None;
+
+    assert!(server_tls_config(&config).is_err());
+}
\ No newline at end of file
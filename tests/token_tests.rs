@@ -0,0 +1,150 @@
+#[allow(dead_code)]
+mod server;
+
+use auth_lib::cp_grpc::SessionRequest;
+use auth_lib::grpc::error_details::{error_reason, ErrorReason};
+use auth_lib::Config;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ed25519_dalek::{Signature, VerifyingKey};
+use server::*;
+use tempfile::TempDir;
+
+fn token_config() -> Config {
+    let mut config = test_config();
+    config.session_tokens = true;
+    config
+}
+
+async fn logged_in(config: Config) -> TestContext {
+    TestContext::with_config(config)
+        .with_algorithm().await
+        .with_registered_user().await
+        .with_challenge().await
+        .with_session().await
+}
+
+fn claims(token: &str) -> serde_json::Value {
+    let claims = token.split('.').nth(1).expect("token has no claims");
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn session_token_carries_claims() {
+    let mut config = token_config();
+    config.session_ttl = 600;
+    let context = logged_in(config).await;
+
+    let claims = claims(context.session.as_ref().unwrap());
+
+    assert_eq!(claims["sub"], context.user.unwrap().user.as_str(), "token belongs to wrong user");
+    assert!(claims["sid"].as_str().is_some_and(|sid| !sid.is_empty()), "token has no session id");
+    assert_eq!(claims["exp"].as_u64().unwrap() - claims["iat"].as_u64().unwrap(), 600, "wrong expiry");
+}
+
+#[tokio::test]
+async fn session_token_can_be_verified_with_published_key() {
+    let context = logged_in(token_config()).await;
+    let token = context.session.unwrap();
+
+    let key = context.server.get_session_key(tonic::Request::new(())).await.unwrap().into_inner();
+    let verifying_key = VerifyingKey::from_bytes(&key.public_key.try_into().unwrap()).unwrap();
+    let (signing_input, signature) = token.rsplit_once('.').unwrap();
+    let signature = Signature::from_slice(&URL_SAFE_NO_PAD.decode(signature).unwrap()).unwrap();
+
+    assert_eq!(key.algorithm, "EdDSA");
+    assert!(key.public_key_pem.starts_with("-----BEGIN PUBLIC KEY-----"), "public key is not PEM encoded");
+    assert!(verifying_key.verify_strict(signing_input.as_bytes(), &signature).is_ok(), "signature is not valid");
+}
+
+#[tokio::test]
+async fn session_token_can_be_validated_and_refreshed() {
+    let context = logged_in(token_config()).await;
+    let token = context.session.unwrap();
+
+    let user = context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: token.clone() })).await;
+    let refreshed = context.server.refresh_session(tonic::Request::new(SessionRequest{ session_id: token.clone() })).await;
+
+    assert_eq!(user.unwrap().into_inner().user, context.user.unwrap().user, "session belongs to wrong user");
+    let refreshed = refreshed.unwrap().into_inner().session_id;
+    assert_ne!(claims(&refreshed)["sid"], claims(&token)["sid"], "session was not replaced");
+    assert!(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: refreshed })).await.is_ok());
+    assert!(context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: token })).await.is_err());
+}
+
+#[tokio::test]
+async fn logout_revokes_session_token() {
+    let context = logged_in(token_config()).await;
+    let token = context.session.unwrap();
+
+    assert!(context.server.logout(tonic::Request::new(SessionRequest{ session_id: token.clone() })).await.is_ok());
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: token })).await;
+
+    assert_eq!(error_reason(&response.unwrap_err()), Some(ErrorReason::SessionNotFound), "wrong error reason");
+}
+
+#[tokio::test]
+async fn changing_secret_revokes_session_tokens() {
+    let context = logged_in(token_config()).await;
+    let token = context.session.clone().unwrap();
+
+    let context = context.with_challenge().await.with_changed_secret(NEW_PASSWORD).await;
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: token })).await;
+
+    assert_eq!(error_reason(&response.unwrap_err()), Some(ErrorReason::SessionNotFound), "token survived the secret change");
+}
+
+#[tokio::test]
+async fn tampered_session_token_is_rejected() {
+    let context = logged_in(token_config()).await;
+    let token = context.session.unwrap();
+    let mut parts: Vec<_> = token.split('.').map(String::from).collect();
+    let mut claims = claims(&token);
+    claims["sub"] = serde_json::Value::from("someone else");
+    parts[1] = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap());
+
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{ session_id: parts.join(".") })).await;
+
+    let status = response.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    assert_eq!(error_reason(&status), Some(ErrorReason::InvalidSessionToken), "wrong error reason");
+}
+
+#[tokio::test]
+async fn plain_session_id_is_rejected_when_tokens_are_enabled() {
+    let context = logged_in(token_config()).await;
+    let session_id = claims(context.session.as_ref().unwrap())["sid"].as_str().unwrap().to_string();
+
+    let response = context.server.validate_session(tonic::Request::new(SessionRequest{ session_id })).await;
+
+    assert_eq!(error_reason(&response.unwrap_err()), Some(ErrorReason::InvalidSessionToken), "wrong error reason");
+}
+
+#[tokio::test]
+async fn session_key_survives_restart() {
+    let directory: TempDir = tempfile::tempdir().unwrap();
+    let config = || {
+        let mut config = token_config();
+        config.session_key_file = Some(directory.path().join("session.pem"));
+        config
+    };
+
+    let first = TestContext::with_config(config());
+    let restarted = TestContext::with_config(config());
+    let first_key = first.server.get_session_key(tonic::Request::new(())).await.unwrap().into_inner();
+    let restarted_key = restarted.server.get_session_key(tonic::Request::new(())).await.unwrap().into_inner();
+
+    assert_eq!(first_key.public_key, restarted_key.public_key, "key was not loaded from the file");
+    assert_eq!(first_key.key_id, restarted_key.key_id, "key id changed");
+}
+
+#[tokio::test]
+async fn session_key_is_not_published_without_tokens() {
+    let context = TestContext::new();
+
+    let response = context.server.get_session_key(tonic::Request::new(())).await;
+
+    let status = response.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    assert_eq!(error_reason(&status), Some(ErrorReason::SessionTokensDisabled), "wrong error reason");
+}
\ No newline at end of file